@@ -1,6 +1,6 @@
 use anyhow::Result;
 use near_api::{signer, Account, AccountId, NearToken, NetworkConfig, RPCEndpoint, Signer, Tokens};
-use near_sandbox_utils::{GenesisAccount, Sandbox};
+use near_sandbox_utils::Sandbox;
 use std::sync::Arc;
 
 #[tokio::main]
@@ -12,7 +12,7 @@ async fn main() -> Result<()> {
         ..NetworkConfig::testnet()
     };
 
-    let genesis_account_default = GenesisAccount::default();
+    let genesis_account_default = &sandbox.genesis_accounts()[0];
     let genesis_account_id: AccountId = genesis_account_default.account_id.parse().unwrap();
     let genesis_signer: Arc<Signer> = Signer::new(Signer::from_secret_key(
         genesis_account_default.private_key.parse().unwrap(),
@@ -5,21 +5,19 @@ use serde_json::json;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let config = SandboxConfig {
-        additional_genesis: Some(json!({
+    let config = SandboxConfig::builder()
+        .additional_genesis(json!({
             "epoch_length": 100,
-        })),
-        additional_accounts: vec![
-            GenesisAccount {
-                account_id: "alice.near".to_string(),
-                public_key: "ed25519:AzBN9XwQDRuLvGvor2JnMitkRxBxn2TLY4yEM3othKUF".to_string(),
-                private_key: "ed25519:5byt6y8h1uuHwkr2ozfN5gt8xGiHujpcT5KyNhZpG62BrnU51sMQk5eTVNwWp7RRiMgKHp7W1jrByxLCr2apXNGB".to_string(),
-                balance: NearToken::from_near(1000).as_yoctonear(),
-            },
-        ],
-        rpc_port: Some(3030),
-        ..Default::default()
-    };
+        }))
+        .add_account(GenesisAccount {
+            account_id: "alice.near".to_string(),
+            public_key: "ed25519:AzBN9XwQDRuLvGvor2JnMitkRxBxn2TLY4yEM3othKUF".to_string(),
+            private_key: "ed25519:5byt6y8h1uuHwkr2ozfN5gt8xGiHujpcT5KyNhZpG62BrnU51sMQk5eTVNwWp7RRiMgKHp7W1jrByxLCr2apXNGB".to_string(),
+            balance: NearToken::from_near(1000).as_yoctonear(),
+            ..Default::default()
+        })
+        .rpc_port(3030)
+        .build();
 
     let sandbox = Sandbox::start_sandbox_with_config(config).await?;
     let network_config = NetworkConfig {
@@ -0,0 +1,88 @@
+//! Helpers built on top of [`near-api`](https://crates.io/crates/near-api), available with the
+//! `near-api` feature.
+
+use std::sync::Arc;
+
+use near_api::{
+    AccountId, NearToken, NetworkConfig, PublicKey, RPCEndpoint, SecretKey, Signer, Tokens,
+};
+
+use super::{GenesisAccount, Sandbox};
+use crate::SandboxError;
+
+impl GenesisAccount {
+    /// Returns the account id and keys of the default genesis account as near-api types.
+    pub fn default_parsed() -> (AccountId, PublicKey, SecretKey) {
+        let account = Self::default();
+        (
+            account
+                .account_id
+                .parse()
+                .expect("default genesis account id is valid"),
+            account
+                .public_key
+                .parse()
+                .expect("default genesis public key is valid"),
+            account
+                .private_key
+                .parse()
+                .expect("default genesis private key is valid"),
+        )
+    }
+}
+
+impl Sandbox {
+    /// Returns a near-api [`NetworkConfig`] pointing to this sandbox.
+    pub fn network_config(&self) -> NetworkConfig {
+        NetworkConfig {
+            network_name: self.network_name.clone(),
+            rpc_endpoints: vec![RPCEndpoint::new(
+                self.rpc_addr.parse().expect("rpc_addr is a valid url"),
+            )],
+            ..NetworkConfig::testnet()
+        }
+    }
+
+    /// Transfers `amount` from the first genesis account to `to` and waits until the transfer
+    /// is final.
+    ///
+    /// Fails with [`SandboxError::InsufficientBalanceError`] if that account can't cover
+    /// `amount`.
+    pub async fn fund(&self, to: &AccountId, amount: NearToken) -> Result<(), SandboxError> {
+        let funder = self.genesis_accounts().first().ok_or_else(|| {
+            SandboxError::TransactionError("sandbox has no genesis account to fund from".into())
+        })?;
+        let funder_id: AccountId = funder
+            .account_id
+            .parse()
+            .map_err(|e| SandboxError::TransactionError(e.to_string()))?;
+        let funder_key: SecretKey = funder
+            .private_key
+            .parse()
+            .map_err(|e| SandboxError::TransactionError(e.to_string()))?;
+        let signer: Arc<Signer> = Signer::new(Signer::from_secret_key(funder_key))
+            .map_err(|e| SandboxError::TransactionError(e.to_string()))?;
+
+        let fund_error = |details: String| {
+            if details.contains("NotEnoughBalance") {
+                SandboxError::InsufficientBalanceError(format!(
+                    "{funder_id} can't send {amount} to {to}: {details}"
+                ))
+            } else {
+                SandboxError::TransactionError(format!("failed to fund {to}: {details}"))
+            }
+        };
+
+        Tokens::account(funder_id.clone())
+            .send_to(to.clone())
+            .near(amount)
+            .with_signer(signer)
+            .send_to(&self.network_config())
+            .await
+            .map_err(|e| fund_error(format!("{e:?}")))?
+            .into_result()
+            .map_err(|e| fund_error(format!("{e:?}")))?;
+
+        Ok(())
+    }
+}
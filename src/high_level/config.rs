@@ -13,17 +13,23 @@
 
 use std::fs::File;
 use std::io::{BufReader, Write};
-use std::path::Path;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
+use base64::Engine;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 
 pub const DEFAULT_GENESIS_ACCOUNT: &str = "sandbox";
 pub const DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY: &str = "ed25519:3tgdk2wPraJzT4nsTuf86UX41xgPNk3MHnq8epARMdBNs29AFEztAuaQ7iHddDfXG9F2RzV1XNQYgJyAyoW51UBB";
 pub const DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY: &str =
     "ed25519:5BGSaf6YjVm7565VzWQHNxoyEjwr3jUpRJSGjREvU9dB";
 pub const DEFAULT_GENESIS_ACCOUNT_BALANCE: u128 = 10_000u128 * 10u128.pow(24);
+pub const DEFAULT_NETWORK_NAME: &str = "sandbox";
 
 #[derive(thiserror::Error, Debug)]
 pub enum SandboxConfigError {
@@ -35,16 +41,29 @@ pub enum SandboxConfigError {
 
     #[error("Invalid environment variables: {0}")]
     EnvParseError(String),
+
+    #[error("Error while reading contract code from {0}: {1}")]
+    ContractFileError(PathBuf, std::io::Error),
+
+    #[error("Invalid genesis contract: {0}")]
+    InvalidContractError(String),
+
+    #[error("Invalid genesis parameter: {0}")]
+    InvalidGenesisError(String),
+
+    #[error("Invalid config parameter: {0}")]
+    InvalidConfigError(String),
 }
 
 #[cfg(feature = "generate")]
-pub(crate) fn random_account_id() -> String {
+pub(crate) fn random_account_id(prefix: &str) -> String {
     use rand::Rng;
 
     let mut rng = rand::thread_rng();
     let random_num = rng.gen_range(10000000000000usize..99999999999999);
     let account_id = format!(
-        "sandbox-genesis-dev-acc-{}-{}",
+        "{}-{}-{}",
+        prefix,
         chrono::Utc::now().format("%Y%m%d%H%M%S"),
         random_num
     );
@@ -86,6 +105,32 @@ pub(crate) fn random_key_pair() -> (String, String) {
     (secret_key, public_key)
 }
 
+/// Returns the implicit account id of an ed25519 public key, i.e. the 64 character hex
+/// encoding of its bytes.
+///
+/// ```rust
+/// let account_id = near_sandbox_utils::implicit_account_id(
+///     "ed25519:5BGSaf6YjVm7565VzWQHNxoyEjwr3jUpRJSGjREvU9dB",
+/// )
+/// .unwrap();
+/// assert_eq!(account_id.len(), 64);
+/// ```
+#[cfg(feature = "generate")]
+pub fn implicit_account_id(public_key: &str) -> Result<String, crate::SandboxError> {
+    use std::convert::TryInto;
+
+    let bytes: [u8; 32] = decode_ed25519(public_key, 32)?
+        .try_into()
+        .expect("length is checked while decoding");
+    ed25519_dalek::VerifyingKey::from_bytes(&bytes).map_err(|e| {
+        SandboxConfigError::InvalidConfigError(format!(
+            "{public_key} is not a valid ed25519 key: {e}"
+        ))
+    })?;
+
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
 /// Genesis account configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GenesisAccount {
@@ -93,6 +138,9 @@ pub struct GenesisAccount {
     pub public_key: String,
     pub private_key: String,
     pub balance: u128,
+    /// Additional full access public keys of the account, besides `public_key`
+    #[serde(default)]
+    pub additional_keys: Vec<String>,
 }
 
 #[cfg(feature = "generate")]
@@ -102,13 +150,23 @@ impl GenesisAccount {
     /// WARNING: Prefer using `GenesisAccount::default()` or defining `GenesisAccount` from a
     /// scratch
     pub fn generate_random() -> Self {
+        Self::generate_random_with("sandbox-genesis-dev-acc", DEFAULT_GENESIS_ACCOUNT_BALANCE)
+    }
+
+    /// Generates pseudo-random genesis account named `{prefix}-{timestamp}-{random number}`
+    /// with the given `balance`, in yoctoNEAR.
+    ///
+    /// The account id takes 30 characters besides the prefix, so the prefix must be at most 34
+    /// characters long to stay within the 64 characters neard allows.
+    pub fn generate_random_with(prefix: &str, balance: u128) -> Self {
         let (private_key, public_key) = random_key_pair();
 
         Self {
-            account_id: random_account_id(),
+            account_id: random_account_id(prefix),
             public_key,
             private_key,
-            balance: DEFAULT_GENESIS_ACCOUNT_BALANCE,
+            balance,
+            additional_keys: Vec::new(),
         }
     }
 }
@@ -120,33 +178,1003 @@ impl Default for GenesisAccount {
             public_key: DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY.to_string(),
             private_key: DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY.to_string(),
             balance: DEFAULT_GENESIS_ACCOUNT_BALANCE,
+            additional_keys: Vec::new(),
+        }
+    }
+}
+
+/// Ed25519 key pair in the `node_key.json`/`validator_key.json` format of neard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyFile {
+    /// Account the key belongs to. neard ignores it for the node key.
+    pub account_id: String,
+    /// Public key, e.g. `ed25519:...`
+    pub public_key: String,
+    /// Secret key, e.g. `ed25519:...`
+    pub secret_key: String,
+}
+
+impl KeyFile {
+    /// Checks that both keys are well-formed ed25519 keys and that `public_key` is the public
+    /// half of `secret_key`.
+    pub fn validate(&self) -> Result<(), SandboxConfigError> {
+        let public_key = decode_ed25519(&self.public_key, 32)?;
+        let secret_key = decode_ed25519(&self.secret_key, 64)?;
+        if secret_key[32..] != public_key[..] {
+            return Err(SandboxConfigError::InvalidConfigError(format!(
+                "public key {} doesn't match the secret key",
+                self.public_key
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, path: impl AsRef<Path>) -> Result<(), SandboxConfigError> {
+        write_json_atomically(path, self)
+    }
+}
+
+fn decode_ed25519(key: &str, len: usize) -> Result<Vec<u8>, SandboxConfigError> {
+    let bytes = key
+        .strip_prefix("ed25519:")
+        .and_then(|key| bs58::decode(key).into_vec().ok())
+        .filter(|bytes| bytes.len() == len)
+        .ok_or_else(|| {
+            SandboxConfigError::InvalidConfigError(format!("{key} is not a valid ed25519 key"))
+        })?;
+
+    Ok(bytes)
+}
+
+/// Contract deployed to one of the genesis accounts, so it already exists at block 0.
+#[derive(Debug, Clone, Default)]
+pub struct GenesisContract {
+    /// Account the contract is deployed to. Must be one of the genesis accounts.
+    pub account_id: String,
+    /// Compiled wasm code of the contract
+    pub code: Vec<u8>,
+    /// Path to a compiled `.wasm` file. Takes precedence over `code` when set.
+    pub code_path: Option<PathBuf>,
+}
+
+impl GenesisContract {
+    /// Returns the wasm code of the contract, reading it from `code_path` if set.
+    pub fn load_code(&self) -> Result<Vec<u8>, SandboxConfigError> {
+        let code = match &self.code_path {
+            Some(path) => std::fs::read(path)
+                .map_err(|e| SandboxConfigError::ContractFileError(path.clone(), e))?,
+            None => self.code.clone(),
+        };
+
+        if !code.starts_with(WASM_MAGIC) {
+            return Err(SandboxConfigError::InvalidContractError(format!(
+                "code for {} is not a valid wasm module",
+                self.account_id
+            )));
         }
+
+        Ok(code)
+    }
+}
+
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// Callback invoked with the attempt number (starting at 1) on every readiness poll
+/// while the sandbox is starting up.
+///
+/// ```rust,no_run
+/// use near_sandbox_utils::{ReadyPollHook, SandboxConfig};
+///
+/// let mut config = SandboxConfig::default();
+/// config.on_ready_poll = Some(ReadyPollHook::new(|attempt| eprintln!("waiting for sandbox: #{attempt}")));
+/// ```
+#[derive(Clone)]
+pub struct ReadyPollHook(Arc<dyn Fn(u32) + Send + Sync>);
+
+impl ReadyPollHook {
+    pub fn new(hook: impl Fn(u32) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+
+    pub(crate) fn call(&self, attempt: u32) {
+        (self.0)(attempt)
+    }
+}
+
+impl std::fmt::Debug for ReadyPollHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ReadyPollHook")
     }
 }
 
+/// Commonly tuned genesis parameters, validated and written into the genesis before
+/// [`SandboxConfig::additional_genesis`] is merged, so the raw JSON still wins for any key
+/// set in both.
+#[derive(Debug, Clone, Default)]
+pub struct GenesisParams {
+    /// Chain id of the network
+    pub chain_id: Option<String>,
+    /// Protocol version the chain starts with
+    pub protocol_version: Option<u32>,
+    /// Height of the genesis block
+    pub genesis_height: Option<u64>,
+    /// Number of blocks in an epoch
+    pub epoch_length: Option<u64>,
+    /// Gas limit of a chunk
+    pub gas_limit: Option<u64>,
+    /// Minimum gas price, in yoctoNEAR
+    pub min_gas_price: Option<u128>,
+    /// Maximum gas price, in yoctoNEAR
+    pub max_gas_price: Option<u128>,
+    /// Number of blocks a transaction stays valid for after its reference block
+    pub transaction_validity_period: Option<u64>,
+    /// Number of block producer seats per epoch
+    pub num_block_producer_seats: Option<u64>,
+    /// Minimum number of validators assigned to each shard. Must not exceed the block
+    /// producer seats or the number of genesis validators.
+    pub minimum_validators_per_shard: Option<u64>,
+    /// Percentage (0-100) of expected blocks a block producer must produce to not be kicked out
+    pub block_producer_kickout_threshold: Option<u8>,
+    /// Percentage (0-100) of expected chunks a chunk producer must produce to not be kicked out
+    pub chunk_producer_kickout_threshold: Option<u8>,
+    /// Economics parameters of the genesis
+    pub economics: GenesisEconomics,
+}
+
+impl GenesisParams {
+    fn to_genesis_patch(&self) -> Result<Value, SandboxConfigError> {
+        let mut patch = serde_json::Map::new();
+
+        if let Some(chain_id) = &self.chain_id {
+            if chain_id.is_empty() {
+                return Err(SandboxConfigError::InvalidGenesisError(
+                    "chain_id must not be empty".to_string(),
+                ));
+            }
+            patch.insert("chain_id".to_string(), Value::String(chain_id.clone()));
+        }
+        if let Some(protocol_version) = self.protocol_version {
+            patch.insert("protocol_version".to_string(), protocol_version.into());
+        }
+        if let Some(genesis_height) = self.genesis_height {
+            patch.insert("genesis_height".to_string(), genesis_height.into());
+        }
+        if let Some(epoch_length) = self.epoch_length {
+            positive("epoch_length", epoch_length)?;
+            patch.insert("epoch_length".to_string(), epoch_length.into());
+        }
+        if let Some(gas_limit) = self.gas_limit {
+            positive("gas_limit", gas_limit)?;
+            patch.insert("gas_limit".to_string(), gas_limit.into());
+        }
+        if let (Some(min), Some(max)) = (self.min_gas_price, self.max_gas_price) {
+            if min > max {
+                return Err(SandboxConfigError::InvalidGenesisError(format!(
+                    "min_gas_price ({min}) must not be greater than max_gas_price ({max})"
+                )));
+            }
+        }
+        if let Some(price) = self.min_gas_price {
+            patch.insert(
+                "min_gas_price".to_string(),
+                Value::String(price.to_string()),
+            );
+        }
+        if let Some(price) = self.max_gas_price {
+            patch.insert(
+                "max_gas_price".to_string(),
+                Value::String(price.to_string()),
+            );
+        }
+        if let Some(period) = self.transaction_validity_period {
+            positive("transaction_validity_period", period)?;
+            patch.insert("transaction_validity_period".to_string(), period.into());
+        }
+        if let Some(seats) = self.num_block_producer_seats {
+            positive("num_block_producer_seats", seats)?;
+            patch.insert("num_block_producer_seats".to_string(), seats.into());
+        }
+        if let Some(min_validators) = self.minimum_validators_per_shard {
+            positive("minimum_validators_per_shard", min_validators)?;
+            patch.insert(
+                "minimum_validators_per_shard".to_string(),
+                min_validators.into(),
+            );
+        }
+        for (name, threshold) in [
+            (
+                "block_producer_kickout_threshold",
+                self.block_producer_kickout_threshold,
+            ),
+            (
+                "chunk_producer_kickout_threshold",
+                self.chunk_producer_kickout_threshold,
+            ),
+        ] {
+            if let Some(threshold) = threshold {
+                if threshold > 100 {
+                    return Err(SandboxConfigError::InvalidGenesisError(format!(
+                        "{name} must be a percentage between 0 and 100, got {threshold}"
+                    )));
+                }
+                patch.insert(name.to_string(), threshold.into());
+            }
+        }
+
+        let mut patch = Value::Object(patch);
+        json_patch::merge(&mut patch, &self.economics.to_genesis_patch()?);
+        Ok(patch)
+    }
+}
+
+/// Ensures `minimum_validators_per_shard` can be satisfied by the final genesis, otherwise
+/// the validator set would be unelectable and neard would fail the first epoch.
+fn check_validators_per_shard(genesis: &Value) -> Result<(), SandboxConfigError> {
+    let Some(min_validators) = genesis["minimum_validators_per_shard"].as_u64() else {
+        return Ok(());
+    };
+
+    if let Some(seats) = genesis["num_block_producer_seats"].as_u64() {
+        if min_validators > seats {
+            return Err(SandboxConfigError::InvalidGenesisError(format!(
+                "minimum_validators_per_shard ({min_validators}) must not be greater than num_block_producer_seats ({seats})"
+            )));
+        }
+    }
+    if let Some(validators) = genesis["validators"].as_array() {
+        if min_validators > validators.len() as u64 {
+            return Err(SandboxConfigError::InvalidGenesisError(format!(
+                "minimum_validators_per_shard ({min_validators}) must not be greater than the number of genesis validators ({})",
+                validators.len()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn positive(name: &str, value: u64) -> Result<(), SandboxConfigError> {
+    if value == 0 {
+        return Err(SandboxConfigError::InvalidGenesisError(format!(
+            "{name} must be positive"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Economics parameters written into the genesis.
+///
+/// Rates are `(numerator, denominator)` pairs, matching the rational `[num, denom]`
+/// representation neard uses in `genesis.json`.
+///
+/// Runtime parameters such as the storage price (`storage_amount_per_byte`) aren't part of
+/// it: neard takes them from its built-in protocol config for the genesis protocol version
+/// and ignores them in `genesis.json`, so they can't be changed through the genesis.
+#[derive(Debug, Clone, Default)]
+pub struct GenesisEconomics {
+    /// Minimum stake, in yoctoNEAR, required to become a fisherman
+    pub fishermen_threshold: Option<u128>,
+    /// Share of the epoch reward that goes to the protocol treasury
+    pub protocol_reward_rate: Option<(u64, u64)>,
+    /// Maximum yearly inflation of the total supply
+    pub max_inflation_rate: Option<(u64, u64)>,
+    /// Expected number of blocks per year, used to compute the per-epoch reward
+    pub num_blocks_per_year: Option<u64>,
+    /// How fast the gas price moves between blocks depending on chunk fullness
+    pub gas_price_adjustment_rate: Option<(u64, u64)>,
+    /// Online ratio below which a validator gets no reward
+    pub online_min_threshold: Option<(u64, u64)>,
+    /// Online ratio above which a validator gets the full reward.
+    /// Must not be lower than [`Self::online_min_threshold`].
+    pub online_max_threshold: Option<(u64, u64)>,
+}
+
+impl GenesisEconomics {
+    fn to_genesis_patch(&self) -> Result<Value, SandboxConfigError> {
+        let mut patch = serde_json::Map::new();
+
+        if let Some(threshold) = self.fishermen_threshold {
+            patch.insert(
+                "fishermen_threshold".to_string(),
+                Value::String(threshold.to_string()),
+            );
+        }
+        if let Some(rate) = self.protocol_reward_rate {
+            patch.insert(
+                "protocol_reward_rate".to_string(),
+                fraction("protocol_reward_rate", rate)?,
+            );
+        }
+        if let Some(rate) = self.max_inflation_rate {
+            patch.insert(
+                "max_inflation_rate".to_string(),
+                fraction("max_inflation_rate", rate)?,
+            );
+        }
+        if let Some(num_blocks) = self.num_blocks_per_year {
+            positive("num_blocks_per_year", num_blocks)?;
+            patch.insert("num_blocks_per_year".to_string(), num_blocks.into());
+        }
+        if let Some(rate) = self.gas_price_adjustment_rate {
+            if rate.0 == 0 {
+                return Err(SandboxConfigError::InvalidGenesisError(
+                    "gas_price_adjustment_rate numerator must be positive".to_string(),
+                ));
+            }
+            patch.insert(
+                "gas_price_adjustment_rate".to_string(),
+                rational("gas_price_adjustment_rate", rate)?,
+            );
+        }
+        if let (Some(min), Some(max)) = (self.online_min_threshold, self.online_max_threshold) {
+            // Cross-multiply to compare the rationals without losing precision.
+            if min.1 != 0
+                && max.1 != 0
+                && min.0 as u128 * max.1 as u128 > max.0 as u128 * min.1 as u128
+            {
+                return Err(SandboxConfigError::InvalidGenesisError(format!(
+                    "online_min_threshold ({}/{}) must not be greater than online_max_threshold ({}/{})",
+                    min.0, min.1, max.0, max.1
+                )));
+            }
+        }
+        if let Some(threshold) = self.online_min_threshold {
+            patch.insert(
+                "online_min_threshold".to_string(),
+                fraction("online_min_threshold", threshold)?,
+            );
+        }
+        if let Some(threshold) = self.online_max_threshold {
+            patch.insert(
+                "online_max_threshold".to_string(),
+                fraction("online_max_threshold", threshold)?,
+            );
+        }
+
+        Ok(Value::Object(patch))
+    }
+}
+
+/// Converts `(numerator, denominator)` into the `[num, denom]` array used by neard.
+fn rational(name: &str, (numerator, denominator): (u64, u64)) -> Result<Value, SandboxConfigError> {
+    if denominator == 0 {
+        return Err(SandboxConfigError::InvalidGenesisError(format!(
+            "{name} denominator must not be zero"
+        )));
+    }
+
+    Ok(serde_json::json!([numerator, denominator]))
+}
+
+/// Same as [`rational`], but additionally checks that the value is within `0..=1`.
+fn fraction(name: &str, rate: (u64, u64)) -> Result<Value, SandboxConfigError> {
+    let value = rational(name, rate)?;
+    if rate.0 > rate.1 {
+        return Err(SandboxConfigError::InvalidGenesisError(format!(
+            "{name} must not be greater than 1, got {}/{}",
+            rate.0, rate.1
+        )));
+    }
+
+    Ok(value)
+}
+
+/// Block production delay set by [`SandboxConfigBuilder::fast_blocks`].
+pub const FAST_BLOCK_PRODUCTION_DELAY: Duration = Duration::from_millis(100);
+
+/// Shortest block production delay written into the config, shorter ones only burn CPU.
+const MIN_BLOCK_PRODUCTION_DELAY: Duration = Duration::from_millis(10);
+
+/// Converts `duration` into the `{ "secs", "nanos" }` representation neard uses in `config.json`.
+fn duration_json(duration: Duration) -> Value {
+    serde_json::json!({
+        "secs": duration.as_secs(),
+        "nanos": duration.subsec_nanos(),
+    })
+}
+
+/// Default number of log lines kept in memory when logs are captured.
+pub const DEFAULT_MAX_LOG_LINES: usize = 10_000;
+
+/// Where the stdout/stderr of the sandbox process goes.
+#[derive(Debug, Clone, Default)]
+pub enum LogOutput {
+    /// Inherit stdout/stderr of the current process
+    #[default]
+    Inherit,
+    /// Capture the output into an in-memory buffer available through [`Sandbox::logs`](crate::Sandbox::logs)
+    ///
+    /// neard only writes its human-readable log format, there's no JSON output to switch to.
+    /// Lines are `<timestamp> <LEVEL> <target>: <message>`, which is what
+    /// [`Sandbox::assert_no_errors`](crate::Sandbox::assert_no_errors) relies on.
+    Captured,
+    /// Append the output to the given file, created if it doesn't exist
+    File(PathBuf),
+}
+
 /// Configuration for the sandbox
+///
+/// Build it with [`SandboxConfig::builder`] or start from [`SandboxConfig::default`] and set
+/// the fields, new fields may be added without a breaking release.
+///
+/// ```rust
+/// use near_sandbox_utils::{GenesisAccount, SandboxConfig};
+/// use serde_json::json;
+///
+/// let config = SandboxConfig::builder()
+///     .rpc_port(3030)
+///     .epoch_length(200)
+///     .add_account(GenesisAccount {
+///         account_id: "alice.near".to_string(),
+///         ..Default::default()
+///     })
+///     .additional_config(json!({ "network": { "trusted_stun_servers": [] } }))
+///     .build();
+/// ```
 #[derive(Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct SandboxConfig {
-    /// Maximum payload size for JSON RPC requests in bytes
+    /// Maximum payload size for JSON RPC requests in bytes.
+    /// Falls back to `NEAR_SANDBOX_MAX_PAYLOAD_SIZE`, then to 1GB.
     pub max_payload_size: Option<usize>,
-    /// Maximum number of open files
+    /// Maximum number of open files.
+    /// Falls back to `NEAR_SANDBOX_MAX_FILES`, then to 3000.
     pub max_open_files: Option<usize>,
+    /// Whether to raise the soft `RLIMIT_NOFILE` of the current process, inherited by the
+    /// sandbox, so it fits `max_open_files`. Defaults to `true`, only applies to unix.
+    pub raise_open_files_limit: Option<bool>,
+    /// Gas limit of view calls, written into `max_gas_burnt_view`. neard's default is used if not set.
+    pub max_gas_burnt_view: Option<u64>,
+    /// Origins allowed to access the RPC from a browser, written into `rpc.cors_allowed_origins`.
+    /// neard's default (`["*"]`) is kept if not set.
+    pub rpc_cors: Option<Vec<String>>,
+    /// Whether neard produces blocks without transactions, written into `produce_empty_blocks`.
+    /// neard's default (`true`) is kept if not set.
+    ///
+    /// When `false`, the height only advances when transactions are sent, so waiting for a
+    /// height on an idle chain never finishes. Startup isn't affected, readiness is detected
+    /// through the `status` RPC rather than block production.
+    pub produce_empty_blocks: Option<bool>,
+    /// Minimum delay between blocks, written into `consensus.min_block_production_delay`, with
+    /// `consensus.max_block_production_delay` set to twice as much. neard's defaults (600ms and
+    /// 2s) are kept if not set, and delays below 10ms are raised to 10ms.
+    ///
+    /// A short delay like [`FAST_BLOCK_PRODUCTION_DELAY`] makes transactions final almost
+    /// instantly, at the cost of neard spinning the CPU.
+    pub block_production_delay: Option<Duration>,
     /// Additional JSON configuration to merge with the default config
     pub additional_config: Option<Value>,
     /// Additional accounts to add to the genesis
     pub additional_accounts: Vec<GenesisAccount>,
+    /// Whether to add [`GenesisAccount::default()`] to the genesis. Defaults to `true`.
+    ///
+    /// When `false`, `additional_accounts` are the only funded accounts, so at least one is required.
+    pub include_default_account: Option<bool>,
+    /// Contracts to deploy to the genesis accounts
+    pub genesis_contracts: Vec<GenesisContract>,
+    /// State records appended verbatim to the genesis `records`, e.g. accounts copied from
+    /// mainnet with [`fetch_account_records`](crate::fetch_account_records). Only `Account`,
+    /// `AccessKey`, `Contract` and `Data` records are accepted, and the amounts of `Account`
+    /// records are added to `total_supply`.
+    pub imported_records: Vec<Value>,
+    /// Typed genesis parameters. Applied before `additional_genesis`, so a key set in both
+    /// takes the value from `additional_genesis`.
+    pub genesis: GenesisParams,
     /// Additional JSON configuration to merge with the genesis
     pub additional_genesis: Option<Value>,
+    /// Exact `total_supply` of the genesis, in yoctoNEAR, instead of the one computed by adding
+    /// the balances of the genesis accounts. Must be at least the sum of all account balances.
+    /// Takes precedence over `total_supply` in `additional_genesis`.
+    pub total_supply: Option<u128>,
+    /// Genesis copied verbatim into the home directory instead of the one generated by
+    /// `neard init`, e.g. the `genesis.json` of another sandbox to join its chain. Can't be
+    /// combined with any of the options changing the genesis.
+    pub genesis_file: Option<PathBuf>,
+    /// Name of the network used by helpers such as `Sandbox::network_config`. Defaults to
+    /// [`GenesisParams::chain_id`] if set, `"sandbox"` otherwise.
+    pub network_name: Option<String>,
+    /// Pre-downloaded sandbox binary to use instead of downloading one, e.g. in air-gapped CI.
+    /// `NEAR_SANDBOX_BIN_PATH` does the same for all sandboxes, including the low-level API.
+    pub binary_path: Option<PathBuf>,
     /// Port that RPC will be bound to. Will be picked randomly if not set.
     pub rpc_port: Option<u16>,
     /// Port that Network will be bound to. Will be picked randomly if not set.
     pub net_port: Option<u16>,
+    /// Port neard serves its Prometheus metrics on, at `/metrics`. Metrics are still served on
+    /// the RPC port if not set.
+    pub metrics_port: Option<u16>,
+    /// Address the RPC is bound to, e.g. `0.0.0.0` to expose it to other containers. Defaults
+    /// to `127.0.0.1`, which also avoids firewall popups on MacOS.
+    pub rpc_host: Option<IpAddr>,
+    /// Address the network is bound to. Defaults to `127.0.0.1`.
+    pub net_host: Option<IpAddr>,
+    /// Nodes to peer with, in the `ed25519:<public key>@<ip>:<port>` format neard expects.
+    pub boot_nodes: Vec<String>,
+    /// Whether the node is a block producing validator. Defaults to `true`.
+    ///
+    /// When `false`, the validator key is removed, so the node only serves RPC. This only makes
+    /// sense when peered through `boot_nodes` with a node producing blocks, whose genesis has to
+    /// be shared through `genesis_file`, so both are required.
+    pub validator: Option<bool>,
+    /// Node key used instead of the random one generated by `neard init`, so the peer id in
+    /// `boot_nodes` of other sandboxes stays the same across runs.
+    pub node_key: Option<KeyFile>,
+    /// Validator key used instead of the random one generated by `neard init`.
+    ///
+    /// `account_id` must be one of the genesis validators, whose staking key is replaced
+    /// with `public_key`.
+    pub validator_key: Option<KeyFile>,
+    /// Maximum time the sandbox may take to answer `/status` once started.
+    /// Falls back to `NEAR_RPC_TIMEOUT_SECS`, then to 10 seconds.
+    pub startup_timeout: Option<Duration>,
+    /// Maximum time `neard init` may take before it's killed and startup fails.
+    /// Falls back to `NEAR_SANDBOX_INIT_TIMEOUT_SECS`, then to 60 seconds.
+    pub init_timeout: Option<Duration>,
+    /// Time [`Sandbox::stop`](crate::Sandbox::stop) waits for neard to exit before killing it.
+    /// Falls back to `NEAR_SANDBOX_SHUTDOWN_SECS`, then to 5 seconds.
+    pub shutdown_grace_period: Option<Duration>,
+    /// Directory to use as the sandbox home directory instead of a fresh temporary one. It's
+    /// kept once the sandbox is dropped.
+    ///
+    /// If it already holds a `genesis.json`, `neard init` and all config and genesis changes
+    /// are skipped and the sandbox resumes from the existing chain state.
+    pub home_dir: Option<PathBuf>,
+    /// Place the sandbox home directory, including the RocksDB store, on tmpfs (`/dev/shm`).
+    ///
+    /// Trades durability for speed, which is what short-lived tests want. Only supported on
+    /// Linux, other platforms fall back to the regular temp dir with a warning.
+    pub in_memory_store: bool,
+    /// Directory the temporary home directory, including the RocksDB store, is created in,
+    /// e.g. a ramdisk mounted on MacOS. Like the default temp dir, it's removed once the
+    /// sandbox is dropped.
+    pub store_path: Option<PathBuf>,
+    /// Release the RPC and network port locks as soon as the sandbox is ready, instead of
+    /// holding them for the whole lifetime of [`Sandbox`](crate::Sandbox).
+    ///
+    /// neard owns the sockets once it's ready, so other sandboxes can't bind them anyway. The
+    /// ports can however be handed out again if this sandbox's process dies while still in use.
+    pub release_port_locks_on_ready: bool,
+    /// Hook called on every readiness poll attempt during startup.
+    pub on_ready_poll: Option<ReadyPollHook>,
+    /// Where the output of the sandbox process goes. Inherited by default.
+    pub log_output: LogOutput,
+    /// Maximum number of captured log lines kept in memory, oldest lines are dropped first.
+    /// Defaults to [`DEFAULT_MAX_LOG_LINES`].
+    pub max_log_lines: Option<usize>,
+    /// Value of `RUST_BACKTRACE` passed to the sandbox process, e.g. `"1"` or `"full"`.
+    /// Left unset by default.
+    pub rust_backtrace: Option<String>,
+}
+
+/// Chainable builder of a [`SandboxConfig`], see [`SandboxConfig::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct SandboxConfigBuilder {
+    config: SandboxConfig,
+}
+
+impl SandboxConfigBuilder {
+    /// Maximum payload size for JSON RPC requests in bytes
+    pub fn max_payload_size(&mut self, size: usize) -> &mut Self {
+        self.config.max_payload_size = Some(size);
+        self
+    }
+
+    /// Maximum number of open files
+    pub fn max_open_files(&mut self, max_open_files: usize) -> &mut Self {
+        self.config.max_open_files = Some(max_open_files);
+        self
+    }
+
+    /// Whether to raise the open files limit to fit `max_open_files`
+    pub fn raise_open_files_limit(&mut self, raise: bool) -> &mut Self {
+        self.config.raise_open_files_limit = Some(raise);
+        self
+    }
+
+    /// Gas limit of view calls
+    pub fn max_gas_burnt_view(&mut self, gas: u64) -> &mut Self {
+        self.config.max_gas_burnt_view = Some(gas);
+        self
+    }
+
+    /// Origins allowed to access the RPC from a browser
+    pub fn rpc_cors(&mut self, origins: Vec<String>) -> &mut Self {
+        self.config.rpc_cors = Some(origins);
+        self
+    }
+
+    /// Whether neard produces blocks without transactions
+    pub fn produce_empty_blocks(&mut self, produce: bool) -> &mut Self {
+        self.config.produce_empty_blocks = Some(produce);
+        self
+    }
+
+    /// Minimum delay between blocks
+    pub fn block_production_delay(&mut self, delay: Duration) -> &mut Self {
+        self.config.block_production_delay = Some(delay);
+        self
+    }
+
+    /// Produces blocks every [`FAST_BLOCK_PRODUCTION_DELAY`], so transactions are final almost
+    /// instantly
+    pub fn fast_blocks(&mut self) -> &mut Self {
+        self.block_production_delay(FAST_BLOCK_PRODUCTION_DELAY)
+    }
+
+    /// Additional JSON configuration to merge with the default config
+    pub fn additional_config(&mut self, config: Value) -> &mut Self {
+        self.config.additional_config = Some(config);
+        self
+    }
+
+    /// Adds an account to the genesis
+    pub fn add_account(&mut self, account: GenesisAccount) -> &mut Self {
+        self.config.additional_accounts.push(account);
+        self
+    }
+
+    /// Whether to add [`GenesisAccount::default()`] to the genesis
+    pub fn include_default_account(&mut self, include: bool) -> &mut Self {
+        self.config.include_default_account = Some(include);
+        self
+    }
+
+    /// Adds a contract deployed to one of the genesis accounts
+    pub fn add_genesis_contract(&mut self, contract: GenesisContract) -> &mut Self {
+        self.config.genesis_contracts.push(contract);
+        self
+    }
+
+    /// Appends state records to the genesis
+    pub fn import_records(&mut self, records: impl IntoIterator<Item = Value>) -> &mut Self {
+        self.config.imported_records.extend(records);
+        self
+    }
+
+    /// Typed genesis parameters
+    pub fn genesis(&mut self, genesis: GenesisParams) -> &mut Self {
+        self.config.genesis = genesis;
+        self
+    }
+
+    /// Number of blocks in an epoch
+    pub fn epoch_length(&mut self, epoch_length: u64) -> &mut Self {
+        self.config.genesis.epoch_length = Some(epoch_length);
+        self
+    }
+
+    /// Gas limit of a chunk
+    pub fn gas_limit(&mut self, gas_limit: u64) -> &mut Self {
+        self.config.genesis.gas_limit = Some(gas_limit);
+        self
+    }
+
+    /// Protocol version the chain starts with
+    pub fn protocol_version(&mut self, protocol_version: u32) -> &mut Self {
+        self.config.genesis.protocol_version = Some(protocol_version);
+        self
+    }
+
+    /// Additional JSON configuration to merge with the genesis
+    pub fn additional_genesis(&mut self, genesis: Value) -> &mut Self {
+        self.config.additional_genesis = Some(genesis);
+        self
+    }
+
+    /// Exact `total_supply` of the genesis, in yoctoNEAR
+    pub fn total_supply(&mut self, total_supply: u128) -> &mut Self {
+        self.config.total_supply = Some(total_supply);
+        self
+    }
+
+    /// Name of the network used by helpers such as `Sandbox::network_config`
+    pub fn network_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.config.network_name = Some(name.into());
+        self
+    }
+
+    /// Pre-downloaded sandbox binary to use instead of downloading one
+    pub fn binary_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.config.binary_path = Some(path.into());
+        self
+    }
+
+    /// Port that RPC will be bound to
+    pub fn rpc_port(&mut self, port: u16) -> &mut Self {
+        self.config.rpc_port = Some(port);
+        self
+    }
+
+    /// Port that Network will be bound to
+    pub fn net_port(&mut self, port: u16) -> &mut Self {
+        self.config.net_port = Some(port);
+        self
+    }
+
+    /// Port neard serves its Prometheus metrics on
+    pub fn metrics_port(&mut self, port: u16) -> &mut Self {
+        self.config.metrics_port = Some(port);
+        self
+    }
+
+    /// Address the RPC is bound to
+    pub fn rpc_host(&mut self, host: IpAddr) -> &mut Self {
+        self.config.rpc_host = Some(host);
+        self
+    }
+
+    /// Address the network is bound to
+    pub fn net_host(&mut self, host: IpAddr) -> &mut Self {
+        self.config.net_host = Some(host);
+        self
+    }
+
+    /// Adds a node to peer with
+    pub fn add_boot_node(&mut self, boot_node: impl Into<String>) -> &mut Self {
+        self.config.boot_nodes.push(boot_node.into());
+        self
+    }
+
+    /// Genesis copied verbatim instead of the one generated by `neard init`
+    pub fn genesis_file(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.config.genesis_file = Some(path.into());
+        self
+    }
+
+    /// Whether the node is a block producing validator
+    pub fn validator(&mut self, validator: bool) -> &mut Self {
+        self.config.validator = Some(validator);
+        self
+    }
+
+    /// Node key used instead of the random one generated by `neard init`
+    pub fn node_key(&mut self, key: KeyFile) -> &mut Self {
+        self.config.node_key = Some(key);
+        self
+    }
+
+    /// Validator key used instead of the random one generated by `neard init`
+    pub fn validator_key(&mut self, key: KeyFile) -> &mut Self {
+        self.config.validator_key = Some(key);
+        self
+    }
+
+    /// Maximum time the sandbox may take to answer `/status` once started
+    pub fn startup_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.config.startup_timeout = Some(timeout);
+        self
+    }
+
+    /// Maximum time `neard init` may take
+    pub fn init_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.config.init_timeout = Some(timeout);
+        self
+    }
+
+    /// Time `Sandbox::stop` waits for neard to exit before killing it
+    pub fn shutdown_grace_period(&mut self, grace_period: Duration) -> &mut Self {
+        self.config.shutdown_grace_period = Some(grace_period);
+        self
+    }
+
+    /// Directory to use as the sandbox home directory instead of a fresh temporary one
+    pub fn home_dir(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.config.home_dir = Some(path.into());
+        self
+    }
+
+    /// Place the sandbox home directory on tmpfs
+    pub fn in_memory_store(&mut self, in_memory: bool) -> &mut Self {
+        self.config.in_memory_store = in_memory;
+        self
+    }
+
+    /// Directory the temporary home directory is created in
+    pub fn store_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.config.store_path = Some(path.into());
+        self
+    }
+
+    /// Release the port locks as soon as the sandbox is ready
+    pub fn release_port_locks_on_ready(&mut self, release: bool) -> &mut Self {
+        self.config.release_port_locks_on_ready = release;
+        self
+    }
+
+    /// Hook called with the attempt number on every readiness poll
+    pub fn on_ready_poll(&mut self, hook: impl Fn(u32) + Send + Sync + 'static) -> &mut Self {
+        self.config.on_ready_poll = Some(ReadyPollHook::new(hook));
+        self
+    }
+
+    /// Where the output of the sandbox process goes
+    pub fn log_output(&mut self, log_output: LogOutput) -> &mut Self {
+        self.config.log_output = log_output;
+        self
+    }
+
+    /// Maximum number of captured log lines kept in memory
+    pub fn max_log_lines(&mut self, max_lines: usize) -> &mut Self {
+        self.config.max_log_lines = Some(max_lines);
+        self
+    }
+
+    /// Value of `RUST_BACKTRACE` passed to the sandbox process
+    pub fn rust_backtrace(&mut self, backtrace: impl Into<String>) -> &mut Self {
+        self.config.rust_backtrace = Some(backtrace.into());
+        self
+    }
+
+    /// Returns the configured [`SandboxConfig`]
+    pub fn build(&self) -> SandboxConfig {
+        self.config.clone()
+    }
+}
+
+impl SandboxConfig {
+    /// Returns a builder starting from the default configuration.
+    pub fn builder() -> SandboxConfigBuilder {
+        SandboxConfigBuilder::default()
+    }
+
+    /// Returns the default configuration with the fields that have an environment variable
+    /// fallback read from the environment:
+    ///
+    /// - `NEAR_SANDBOX_MAX_PAYLOAD_SIZE`: [`Self::max_payload_size`]
+    /// - `NEAR_SANDBOX_MAX_FILES`: [`Self::max_open_files`]
+    /// - `NEAR_RPC_TIMEOUT_SECS`: [`Self::startup_timeout`]
+    /// - `NEAR_SANDBOX_INIT_TIMEOUT_SECS`: [`Self::init_timeout`]
+    /// - `NEAR_SANDBOX_SHUTDOWN_SECS`: [`Self::shutdown_grace_period`]
+    ///
+    /// Variables about the binary and logging are shared with the low-level API and keep being
+    /// read where they're used. Fails with [`SandboxConfigError::EnvParseError`] on malformed
+    /// values.
+    pub fn from_env() -> Result<Self, SandboxConfigError> {
+        let secs = |env_var: &str| {
+            Ok::<_, SandboxConfigError>(parse_env(env_var)?.map(Duration::from_secs))
+        };
+
+        Ok(Self {
+            max_payload_size: parse_env("NEAR_SANDBOX_MAX_PAYLOAD_SIZE")?,
+            max_open_files: parse_env("NEAR_SANDBOX_MAX_FILES")?,
+            startup_timeout: secs("NEAR_RPC_TIMEOUT_SECS")?,
+            init_timeout: secs("NEAR_SANDBOX_INIT_TIMEOUT_SECS")?,
+            shutdown_grace_period: secs("NEAR_SANDBOX_SHUTDOWN_SECS")?,
+            ..Default::default()
+        })
+    }
+
+    /// Fills the fields left unset with the values from [`Self::from_env`].
+    pub(crate) fn with_env(mut self) -> Result<Self, SandboxConfigError> {
+        let env = Self::from_env()?;
+        self.max_payload_size = self.max_payload_size.or(env.max_payload_size);
+        self.max_open_files = self.max_open_files.or(env.max_open_files);
+        self.startup_timeout = self.startup_timeout.or(env.startup_timeout);
+        self.init_timeout = self.init_timeout.or(env.init_timeout);
+        self.shutdown_grace_period = self.shutdown_grace_period.or(env.shutdown_grace_period);
+        Ok(self)
+    }
+
+    /// Checks the configuration for mistakes that would otherwise only surface once the
+    /// sandbox process is started, such as conflicting ports.
+    pub fn validate(&self) -> Result<(), SandboxConfigError> {
+        for (name, port) in [("rpc_port", self.rpc_port), ("net_port", self.net_port)] {
+            if port == Some(0) {
+                return Err(SandboxConfigError::InvalidConfigError(format!(
+                    "{name} must not be 0, leave it unset to pick a random port"
+                )));
+            }
+        }
+        if self.metrics_port == Some(0) {
+            return Err(SandboxConfigError::InvalidConfigError(
+                "metrics_port must not be 0".to_string(),
+            ));
+        }
+
+        let ports = [
+            ("rpc_port", self.rpc_port),
+            ("net_port", self.net_port),
+            ("metrics_port", self.metrics_port),
+        ];
+        for (i, (name, port)) in ports.iter().enumerate() {
+            for (other_name, other_port) in &ports[i + 1..] {
+                if let (Some(port), Some(other_port)) = (port, other_port) {
+                    if port == other_port {
+                        return Err(SandboxConfigError::InvalidConfigError(format!(
+                            "{name} and {other_name} must differ, both are {port}"
+                        )));
+                    }
+                }
+            }
+        }
+
+        for key in self.node_key.iter().chain(self.validator_key.iter()) {
+            key.validate()?;
+        }
+        let home_dir_options = [
+            ("home_dir", self.home_dir.is_some()),
+            ("in_memory_store", self.in_memory_store),
+            ("store_path", self.store_path.is_some()),
+        ];
+        for (i, (name, is_set)) in home_dir_options.iter().enumerate() {
+            for (other_name, other_is_set) in &home_dir_options[i + 1..] {
+                if *is_set && *other_is_set {
+                    return Err(SandboxConfigError::InvalidConfigError(format!(
+                        "{name} and {other_name} can't be combined"
+                    )));
+                }
+            }
+        }
+        if self.genesis_file.is_some() {
+            let genesis_options = [
+                ("additional_accounts", !self.additional_accounts.is_empty()),
+                ("genesis_contracts", !self.genesis_contracts.is_empty()),
+                ("imported_records", !self.imported_records.is_empty()),
+                ("additional_genesis", self.additional_genesis.is_some()),
+                ("total_supply", self.total_supply.is_some()),
+                (
+                    "genesis",
+                    self.genesis
+                        .to_genesis_patch()?
+                        .as_object()
+                        .is_some_and(|patch| !patch.is_empty()),
+                ),
+            ];
+            for (name, is_set) in genesis_options {
+                if is_set {
+                    return Err(SandboxConfigError::InvalidConfigError(format!(
+                        "{name} can't be combined with genesis_file, which is used as is"
+                    )));
+                }
+            }
+        }
+        if !self.validator.unwrap_or(true) {
+            if self.validator_key.is_some() {
+                return Err(SandboxConfigError::InvalidConfigError(
+                    "validator_key is set but the sandbox isn't a validator".to_string(),
+                ));
+            }
+            if self.boot_nodes.is_empty() {
+                return Err(SandboxConfigError::InvalidConfigError(
+                    "a non-validator sandbox needs boot_nodes to peer with a block producing node"
+                        .to_string(),
+                ));
+            }
+            if self.genesis_file.is_none() {
+                return Err(SandboxConfigError::InvalidConfigError(
+                    "a non-validator sandbox needs genesis_file, the genesis of the chain it joins"
+                        .to_string(),
+                ));
+            }
+        }
+
+        let configured_rpc_port = self
+            .additional_config
+            .as_ref()
+            .and_then(|config| config["rpc"]["addr"].as_str())
+            .and_then(|addr| addr.rsplit(':').next())
+            .and_then(|port| port.parse::<u16>().ok());
+        if let (Some(rpc_port), Some(configured_port)) = (self.rpc_port, configured_rpc_port) {
+            if rpc_port != configured_port {
+                return Err(SandboxConfigError::InvalidConfigError(format!(
+                    "rpc_port {rpc_port} conflicts with port {configured_port} of rpc.addr in additional_config"
+                )));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 /// Overwrite the $home_dir/config.json file over a set of entries. `value` will be used per (key, value) pair
 /// where value can also be another dict. This recursively sets all entry in `value` dict to the config
 /// dict, and saves back into `home_dir` at the end of the day.
-fn overwrite(home_dir: impl AsRef<Path>, value: Value) -> Result<(), SandboxConfigError> {
+pub(crate) fn overwrite(
+    home_dir: impl AsRef<Path>,
+    value: Value,
+) -> Result<(), SandboxConfigError> {
     let home_dir = home_dir.as_ref();
     let config_file =
         File::open(home_dir.join("config.json")).map_err(SandboxConfigError::FileError)?;
@@ -154,15 +1182,102 @@ fn overwrite(home_dir: impl AsRef<Path>, value: Value) -> Result<(), SandboxConf
     let mut config: Value = serde_json::from_reader(config)?;
 
     json_patch::merge(&mut config, &value);
-    let config_file =
-        File::create(home_dir.join("config.json")).map_err(SandboxConfigError::FileError)?;
-    serde_json::to_writer(config_file, &config)?;
+    check_config(&config)?;
+    write_json_atomically(home_dir.join("config.json"), &config)
+}
+
+/// Checks the keys this crate relies on still have the expected types after merging user JSON
+/// into `config.json`, as neard would otherwise fail to start without a clear error.
+fn check_config(config: &Value) -> Result<(), SandboxConfigError> {
+    for pointer in [
+        "/rpc/limits_config/json_payload_max_size",
+        "/store/max_open_files",
+    ] {
+        if let Some(value) = config.pointer(pointer) {
+            if !value.is_u64() {
+                return Err(SandboxConfigError::InvalidConfigError(format!(
+                    "{} must be a non-negative integer, got {value}",
+                    &pointer[1..].replace('/', ".")
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Same as [`check_config`] for `genesis.json`.
+fn check_genesis(genesis: &Value) -> Result<(), SandboxConfigError> {
+    let invalid = |message: String| Err(SandboxConfigError::InvalidGenesisError(message));
+
+    if !genesis["records"].is_array() {
+        return invalid(format!(
+            "records must be an array, got {}",
+            genesis["records"]
+        ));
+    }
+    let total_supply = &genesis["total_supply"];
+    if total_supply
+        .as_str()
+        .and_then(|supply| u128::from_str(supply).ok())
+        .is_none()
+    {
+        return invalid(format!(
+            "total_supply must be a u128 string, got {total_supply}"
+        ));
+    }
+    if !genesis["chain_id"].is_string() {
+        return invalid(format!(
+            "chain_id must be a string, got {}",
+            genesis["chain_id"]
+        ));
+    }
+    for key in ["epoch_length", "gas_limit", "protocol_version"] {
+        if !genesis[key].is_u64() {
+            return invalid(format!(
+                "{key} must be a non-negative integer, got {}",
+                genesis[key]
+            ));
+        }
+    }
+
+    check_validators_per_shard(genesis)
+}
+
+/// Write `value` to a temporary file next to `path` and rename it over `path`, so an
+/// interrupted write never leaves a half-written file behind. The file, and on unix the rename,
+/// are synced to disk before returning.
+fn write_json_atomically(
+    path: impl AsRef<Path>,
+    value: &impl Serialize,
+) -> Result<(), SandboxConfigError> {
+    let path = path.as_ref();
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir).map_err(SandboxConfigError::FileError)?;
+    let mut writer = std::io::BufWriter::new(&mut file);
+    serde_json::to_writer(&mut writer, value)?;
+    writer.flush().map_err(SandboxConfigError::FileError)?;
+    drop(writer);
+    file.as_file()
+        .sync_all()
+        .map_err(SandboxConfigError::FileError)?;
+    file.persist(path)
+        .map_err(|e| SandboxConfigError::FileError(e.error))?;
+
+    // The rename is only durable once the directory entry is synced as well.
+    #[cfg(unix)]
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(SandboxConfigError::FileError)?;
 
     Ok(())
 }
 
 /// Parse an environment variable or return a default value.
-fn parse_env<T>(env_var: &str) -> Result<Option<T>, SandboxConfigError>
+pub(crate) fn parse_env<T>(env_var: &str) -> Result<Option<T>, SandboxConfigError>
 where
     T: std::str::FromStr,
     T::Err: std::error::Error + Send + Sync + 'static,
@@ -178,6 +1293,11 @@ where
     }
 }
 
+/// Maximum number of files RocksDB keeps open, written into `store.max_open_files`.
+pub(crate) fn max_open_files(config: &SandboxConfig) -> usize {
+    config.max_open_files.unwrap_or(3000) // Default to 3,000
+}
+
 /// Set extra configs for the sandbox with custom configuration.
 ///
 /// # Arguments
@@ -187,15 +1307,9 @@ pub(crate) fn set_sandbox_configs_with_config(
     home_dir: impl AsRef<Path>,
     config: &SandboxConfig,
 ) -> Result<(), SandboxConfigError> {
-    let max_payload_size = config
-        .max_payload_size
-        .or_else(|| parse_env("NEAR_SANDBOX_MAX_PAYLOAD_SIZE").ok().flatten())
-        .unwrap_or(1024 * 1024 * 1024); // Default to 1GB
+    let max_payload_size = config.max_payload_size.unwrap_or(1024 * 1024 * 1024); // Default to 1GB
 
-    let max_open_files = config
-        .max_open_files
-        .or_else(|| parse_env("NEAR_SANDBOX_MAX_FILES").ok().flatten())
-        .unwrap_or(3000); // Default to 3,000
+    let max_open_files = max_open_files(config);
 
     let mut json_config = serde_json::json!({
         "rpc": {
@@ -208,6 +1322,46 @@ pub(crate) fn set_sandbox_configs_with_config(
         }
     });
 
+    if let Some(max_gas_burnt_view) = config.max_gas_burnt_view {
+        if max_gas_burnt_view == 0 {
+            return Err(SandboxConfigError::InvalidConfigError(
+                "max_gas_burnt_view must be positive".to_string(),
+            ));
+        }
+        json_config["max_gas_burnt_view"] = max_gas_burnt_view.into();
+    }
+
+    if let Some(produce_empty_blocks) = config.produce_empty_blocks {
+        json_config["produce_empty_blocks"] = produce_empty_blocks.into();
+    }
+
+    if let Some(delay) = config.block_production_delay {
+        let min_delay = delay.max(MIN_BLOCK_PRODUCTION_DELAY);
+        json_config["consensus"]["min_block_production_delay"] = duration_json(min_delay);
+        json_config["consensus"]["max_block_production_delay"] = duration_json(min_delay * 2);
+    }
+
+    if !config.boot_nodes.is_empty() {
+        json_config["network"]["boot_nodes"] = Value::String(config.boot_nodes.join(","));
+    }
+
+    if let Some(origins) = &config.rpc_cors {
+        if origins.iter().any(|origin| origin.trim().is_empty()) {
+            return Err(SandboxConfigError::InvalidConfigError(
+                "rpc_cors origins must be non-empty strings".to_string(),
+            ));
+        }
+        json_config["rpc"]["cors_allowed_origins"] = serde_json::json!(origins);
+    }
+
+    if let Some(port) = config.metrics_port {
+        let host = config
+            .rpc_host
+            .unwrap_or(IpAddr::V4(std::net::Ipv4Addr::LOCALHOST));
+        json_config["rpc"]["prometheus_addr"] =
+            Value::String(std::net::SocketAddr::new(host, port).to_string());
+    }
+
     // Merge any additional config provided by the user
     if let Some(additional_config) = &config.additional_config {
         json_patch::merge(&mut json_config, additional_config);
@@ -216,6 +1370,52 @@ pub(crate) fn set_sandbox_configs_with_config(
     overwrite(home_dir, json_config)
 }
 
+/// Write the configured node and validator keys into `home_dir`, and remove the validator key
+/// if the sandbox isn't supposed to be a validator. Also applied to a resumed home directory.
+pub(crate) fn set_sandbox_validator_with_config(
+    home_dir: impl AsRef<Path>,
+    config: &SandboxConfig,
+) -> Result<(), SandboxConfigError> {
+    let home_dir = home_dir.as_ref();
+    if let Some(node_key) = &config.node_key {
+        node_key.write(home_dir.join("node_key.json"))?;
+    }
+
+    if config.validator.unwrap_or(true) {
+        if let Some(validator_key) = &config.validator_key {
+            validator_key.write(home_dir.join("validator_key.json"))?;
+        }
+        return Ok(());
+    }
+
+    match std::fs::remove_file(home_dir.join("validator_key.json")) {
+        Ok(()) => Ok(()),
+        // Already removed when the home directory was created.
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(SandboxConfigError::FileError(err)),
+    }
+}
+
+/// Accounts added to the genesis: the default one unless excluded, then `additional_accounts`.
+pub(crate) fn genesis_accounts(
+    config: &SandboxConfig,
+) -> Result<Vec<GenesisAccount>, SandboxConfigError> {
+    let mut accounts = Vec::new();
+    if config.include_default_account.unwrap_or(true) {
+        accounts.push(GenesisAccount::default());
+    }
+    accounts.extend(config.additional_accounts.clone());
+
+    if accounts.is_empty() {
+        return Err(SandboxConfigError::InvalidGenesisError(
+            "no genesis accounts, add additional_accounts when excluding the default account"
+                .to_string(),
+        ));
+    }
+
+    Ok(accounts)
+}
+
 /// Overwrite the $home_dir/genesis.json file over a set of entries. `value` will be used per (key, value) pair
 /// where value can also be another dict. This recursively sets all entry in `value` dict to the config
 /// dict, and saves back into `home_dir` at the end of the day.
@@ -229,21 +1429,58 @@ fn overwrite_genesis(
     let config_reader = BufReader::new(config_file);
     let mut genesis: Value = serde_json::from_reader(config_reader)?;
     let genesis_obj = genesis.as_object_mut().expect("expected to be object");
-    let mut total_supply = u128::from_str(
-        genesis_obj
-            .get_mut("total_supply")
-            .expect("expected exist total_supply")
-            .as_str()
-            .unwrap_or_default(),
-    )
-    .unwrap_or_default();
+    let total_supply = genesis_obj.get("total_supply").unwrap_or(&Value::Null);
+    let mut total_supply = total_supply
+        .as_str()
+        .and_then(|supply| u128::from_str(supply).ok())
+        .ok_or_else(|| {
+            SandboxConfigError::InvalidGenesisError(format!(
+                "expected total_supply to be a u128 string, got {total_supply}"
+            ))
+        })?;
 
-    let mut accounts_to_add = vec![GenesisAccount::default()];
+    let accounts_to_add = genesis_accounts(config)?;
 
-    accounts_to_add.extend(config.additional_accounts.clone());
+    let mut contracts = std::collections::HashMap::new();
+    for contract in &config.genesis_contracts {
+        if !accounts_to_add
+            .iter()
+            .any(|account| account.account_id == contract.account_id)
+        {
+            return Err(SandboxConfigError::InvalidContractError(format!(
+                "{} is not a genesis account",
+                contract.account_id
+            )));
+        }
+        if contracts
+            .insert(contract.account_id.as_str(), contract.load_code()?)
+            .is_some()
+        {
+            return Err(SandboxConfigError::InvalidContractError(format!(
+                "more than one contract deployed to {}",
+                contract.account_id
+            )));
+        }
+    }
+
+    for record in &config.imported_records {
+        let Some(balance) = imported_balance(record, &accounts_to_add)? else {
+            continue;
+        };
+        total_supply = total_supply.checked_add(balance).ok_or_else(|| {
+            SandboxConfigError::InvalidGenesisError(format!(
+                "total_supply overflows u128 when adding imported record {record}"
+            ))
+        })?;
+    }
 
     for account in &accounts_to_add {
-        total_supply += account.balance;
+        total_supply = total_supply.checked_add(account.balance).ok_or_else(|| {
+            SandboxConfigError::InvalidGenesisError(format!(
+                "total_supply overflows u128 when adding the balance of {}",
+                account.account_id
+            ))
+        })?;
     }
 
     genesis_obj.insert(
@@ -257,6 +1494,15 @@ fn overwrite_genesis(
     let records_array = records.as_array_mut().expect("expected to be array");
 
     for account in &accounts_to_add {
+        let code = contracts.get(account.account_id.as_str());
+        let (code_hash, storage_usage) = match code {
+            Some(code) => (
+                bs58::encode(Sha256::digest(code)).into_string(),
+                182 + code.len() as u64,
+            ),
+            None => ("11111111111111111111111111111111".to_string(), 182),
+        };
+
         records_array.push(serde_json::json!(
             {
                 "Account": {
@@ -264,37 +1510,200 @@ fn overwrite_genesis(
                     "account": {
                     "amount": account.balance.to_string(),
                     "locked": "0",
-                    "code_hash": "11111111111111111111111111111111",
-                    "storage_usage": 182
+                    "code_hash": code_hash,
+                    "storage_usage": storage_usage
                     }
                 }
             }
         ));
 
-        records_array.push(serde_json::json!(
-            {
-                "AccessKey": {
-                    "account_id": account.account_id,
-                    "public_key": account.public_key,
-                    "access_key": {
-                    "nonce": 0,
-                    "permission": "FullAccess"
+        if let Some(code) = code {
+            records_array.push(serde_json::json!(
+                {
+                    "Contract": {
+                        "account_id": account.account_id,
+                        "code": base64::engine::general_purpose::STANDARD.encode(code),
                     }
                 }
+            ));
+        }
+
+        let mut public_keys = vec![&account.public_key];
+        for public_key in &account.additional_keys {
+            if public_keys.contains(&public_key) {
+                return Err(SandboxConfigError::InvalidGenesisError(format!(
+                    "duplicate access key {} for {}",
+                    public_key, account.account_id
+                )));
             }
-        ));
+            public_keys.push(public_key);
+        }
+
+        for public_key in public_keys {
+            records_array.push(serde_json::json!(
+                {
+                    "AccessKey": {
+                        "account_id": account.account_id,
+                        "public_key": public_key,
+                        "access_key": {
+                        "nonce": 0,
+                        "permission": "FullAccess"
+                        }
+                    }
+                }
+            ));
+        }
     }
 
+    records_array.extend(config.imported_records.iter().cloned());
+
+    if let Some(validator_key) = &config.validator_key {
+        set_validator_public_key(&mut genesis, validator_key)?;
+    }
+
+    json_patch::merge(&mut genesis, &config.genesis.to_genesis_patch()?);
+
     if let Some(additional_genesis) = &config.additional_genesis {
         json_patch::merge(&mut genesis, additional_genesis);
     }
 
-    let config_file =
-        File::create(home_dir.join("genesis.json")).map_err(SandboxConfigError::FileError)?;
-    serde_json::to_writer(config_file, &genesis)?;
+    if let Some(total_supply) = config.total_supply {
+        set_total_supply(&mut genesis, total_supply)?;
+    }
+
+    check_genesis(&genesis)?;
+
+    write_json_atomically(home_dir.join("genesis.json"), &genesis)
+}
+
+/// Checks that `record` is a well-formed state record that doesn't clash with the generated
+/// genesis accounts, returning the liquid and locked balance it adds if it's an account.
+fn imported_balance(
+    record: &Value,
+    accounts: &[GenesisAccount],
+) -> Result<Option<u128>, SandboxConfigError> {
+    let invalid = |reason: &str| {
+        Err(SandboxConfigError::InvalidGenesisError(format!(
+            "imported record {record} {reason}"
+        )))
+    };
+
+    let Some((kind, body)) = record
+        .as_object()
+        .filter(|record| record.len() == 1)
+        .and_then(|record| record.iter().next())
+    else {
+        return invalid("must be an object with a single record kind");
+    };
+    let Some(account_id) = body["account_id"].as_str() else {
+        return invalid("has no account_id");
+    };
+    match kind.as_str() {
+        "Account" => {
+            if accounts
+                .iter()
+                .any(|account| account.account_id == account_id)
+            {
+                return invalid("duplicates a genesis account");
+            }
+            let mut balance: u128 = 0;
+            for key in ["amount", "locked"] {
+                let Some(amount) = body["account"][key]
+                    .as_str()
+                    .and_then(|amount| amount.parse::<u128>().ok())
+                else {
+                    return invalid(&format!("has no valid account.{key}"));
+                };
+                balance = match balance.checked_add(amount) {
+                    Some(balance) => balance,
+                    None => return invalid("has a balance overflowing u128"),
+                };
+            }
+            if !body["account"]["code_hash"].is_string() {
+                return invalid("has no account.code_hash");
+            }
+            if !body["account"]["storage_usage"].is_u64() {
+                return invalid("has no account.storage_usage");
+            }
+            Ok(Some(balance))
+        }
+        "AccessKey" => {
+            if !body["public_key"].is_string() || !body["access_key"].is_object() {
+                return invalid("must have a public_key and an access_key");
+            }
+            Ok(None)
+        }
+        "Contract" => {
+            if !body["code"].is_string() {
+                return invalid("has no base64 encoded code");
+            }
+            Ok(None)
+        }
+        "Data" => {
+            if !body["data_key"].is_string() || !body["value"].is_string() {
+                return invalid("must have a base64 encoded data_key and value");
+            }
+            Ok(None)
+        }
+        _ => invalid("must be an Account, AccessKey, Contract or Data record"),
+    }
+}
+
+/// Override `total_supply`, which must cover the liquid and locked balances of all accounts in
+/// the genesis records.
+fn set_total_supply(genesis: &mut Value, total_supply: u128) -> Result<(), SandboxConfigError> {
+    let mut required: u128 = 0;
+    for record in genesis["records"].as_array().into_iter().flatten() {
+        let account = &record["Account"]["account"];
+        for balance in [&account["amount"], &account["locked"]] {
+            let balance = balance
+                .as_str()
+                .and_then(|balance| balance.parse::<u128>().ok())
+                .unwrap_or_default();
+            required = required.checked_add(balance).ok_or_else(|| {
+                SandboxConfigError::InvalidGenesisError(
+                    "sum of genesis account balances overflows u128".to_string(),
+                )
+            })?;
+        }
+    }
+
+    if total_supply < required {
+        return Err(SandboxConfigError::InvalidGenesisError(format!(
+            "total_supply ({total_supply}) must be at least the sum of genesis account balances ({required})"
+        )));
+    }
+    genesis["total_supply"] = Value::String(total_supply.to_string());
+
     Ok(())
 }
 
+/// Replace the staking key of the genesis validator `key.account_id` with `key.public_key`, so
+/// the node signs blocks with a key the chain expects.
+fn set_validator_public_key(genesis: &mut Value, key: &KeyFile) -> Result<(), SandboxConfigError> {
+    let validator = genesis["validators"]
+        .as_array_mut()
+        .and_then(|validators| {
+            validators
+                .iter_mut()
+                .find(|validator| validator["account_id"] == key.account_id.as_str())
+        })
+        .ok_or_else(|| {
+            SandboxConfigError::InvalidGenesisError(format!(
+                "validator_key account {} is not a genesis validator",
+                key.account_id
+            ))
+        })?;
+    validator["public_key"] = Value::String(key.public_key.clone());
+
+    Ok(())
+}
+
+/// Path of the key file written for a genesis account.
+pub(crate) fn account_key_path(home_dir: impl AsRef<Path>, account_id: &str) -> PathBuf {
+    home_dir.as_ref().join(format!("{account_id}.json"))
+}
+
 /// Save account keys to individual JSON files
 fn save_account_keys(
     home_dir: impl AsRef<Path>,
@@ -309,14 +1718,7 @@ fn save_account_keys(
             "private_key": account.private_key
         });
 
-        let file_name = format!("{}.json", account.account_id);
-        let mut key_file =
-            File::create(home_dir.join(&file_name)).map_err(SandboxConfigError::FileError)?;
-        let key_content = serde_json::to_string(&key_json)?;
-        key_file
-            .write_all(key_content.as_bytes())
-            .map_err(SandboxConfigError::FileError)?;
-        key_file.flush().map_err(SandboxConfigError::FileError)?;
+        write_json_atomically(account_key_path(home_dir, &account.account_id), &key_json)?;
     }
 
     Ok(())
@@ -331,12 +1733,133 @@ pub fn set_sandbox_genesis_with_config(
     home_dir: impl AsRef<Path>,
     config: &SandboxConfig,
 ) -> Result<(), SandboxConfigError> {
-    overwrite_genesis(&home_dir, config)?;
-
-    let mut all_accounts = vec![GenesisAccount::default()];
-    all_accounts.extend(config.additional_accounts.clone());
+    match &config.genesis_file {
+        Some(genesis_file) => {
+            std::fs::copy(genesis_file, home_dir.as_ref().join("genesis.json"))
+                .map_err(SandboxConfigError::FileError)?;
+        }
+        None => overwrite_genesis(&home_dir, config)?,
+    }
 
-    save_account_keys(&home_dir, &all_accounts)?;
+    save_account_keys(&home_dir, &genesis_accounts(config)?)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overwrite_minimal_genesis(
+        total_supply: &str,
+        config: &SandboxConfig,
+    ) -> Result<Value, SandboxConfigError> {
+        let home_dir = tempfile::tempdir().unwrap();
+        let genesis = serde_json::json!({
+            "chain_id": "test-chain",
+            "epoch_length": 500,
+            "gas_limit": 1_000_000_000_000_000u64,
+            "protocol_version": 73,
+            "total_supply": total_supply,
+            "records": [],
+        });
+        std::fs::write(home_dir.path().join("genesis.json"), genesis.to_string()).unwrap();
+
+        overwrite_genesis(home_dir.path(), config)?;
+        let genesis = std::fs::read_to_string(home_dir.path().join("genesis.json")).unwrap();
+        Ok(serde_json::from_str(&genesis).unwrap())
+    }
+
+    fn account(account_id: &str, balance: u128) -> GenesisAccount {
+        GenesisAccount {
+            account_id: account_id.to_string(),
+            balance,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn total_supply_includes_genesis_account_balances() {
+        let config = SandboxConfig {
+            additional_accounts: vec![account("alice.near", 1_000), account("bob.near", 234)],
+            ..Default::default()
+        };
+
+        let genesis = overwrite_minimal_genesis("100", &config).unwrap();
+
+        let expected = 100 + DEFAULT_GENESIS_ACCOUNT_BALANCE + 1_000 + 234;
+        assert_eq!(genesis["total_supply"], Value::String(expected.to_string()));
+    }
+
+    #[test]
+    fn unparseable_total_supply_is_rejected() {
+        let err = overwrite_minimal_genesis("not a number", &SandboxConfig::default()).unwrap_err();
+
+        match err {
+            SandboxConfigError::InvalidGenesisError(message) => {
+                assert!(
+                    message.contains("expected total_supply to be a u128 string"),
+                    "{message}"
+                );
+            }
+            err => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn total_supply_overflow_is_rejected() {
+        let config = SandboxConfig {
+            additional_accounts: vec![account("alice.near", 1)],
+            include_default_account: Some(false),
+            ..Default::default()
+        };
+
+        let err = overwrite_minimal_genesis(&u128::MAX.to_string(), &config).unwrap_err();
+
+        match err {
+            SandboxConfigError::InvalidGenesisError(message) => {
+                assert!(message.contains("overflows u128"), "{message}");
+                assert!(message.contains("alice.near"), "{message}");
+            }
+            err => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[cfg(feature = "generate")]
+    #[test]
+    fn implicit_account_id_is_the_hex_of_the_public_key() {
+        assert_eq!(
+            implicit_account_id(DEFAULT_GENESIS_ACCOUNT_PUBLIC_KEY).unwrap(),
+            "3e1003d95833cd81d1fe7eb68b9d0a52b4629c4745212031f7705156300f3cfa"
+        );
+        assert!(implicit_account_id("ed25519:not-base58").is_err());
+    }
+
+    #[cfg(feature = "generate")]
+    #[test]
+    fn generated_account_keys_form_a_key_pair() {
+        use std::convert::TryInto;
+
+        let account = GenesisAccount::generate_random_with("alice", 42);
+        assert!(account.account_id.starts_with("alice-"));
+        assert_eq!(account.balance, 42);
+
+        let decode = |key: &str| {
+            let key = key.strip_prefix("ed25519:").unwrap();
+            bs58::decode(key).into_vec().unwrap()
+        };
+        let private_key = decode(&account.private_key);
+        assert_eq!(private_key.len(), ed25519_dalek::KEYPAIR_LENGTH);
+        let seed: [u8; ed25519_dalek::SECRET_KEY_LENGTH] = private_key
+            [..ed25519_dalek::SECRET_KEY_LENGTH]
+            .try_into()
+            .unwrap();
+
+        let derived = ed25519_dalek::SigningKey::from_bytes(&seed).verifying_key();
+        assert_eq!(derived.to_bytes().to_vec(), decode(&account.public_key));
+        assert_eq!(
+            private_key[ed25519_dalek::SECRET_KEY_LENGTH..],
+            derived.to_bytes()
+        );
+    }
+}
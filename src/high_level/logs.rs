@@ -0,0 +1,82 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Child;
+
+/// In-memory ring buffer of the sandbox process output.
+#[derive(Debug, Clone)]
+pub(crate) struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    max_lines: usize,
+}
+
+impl LogBuffer {
+    pub(crate) fn new(max_lines: usize) -> Self {
+        Self {
+            lines: Arc::new(Mutex::new(VecDeque::new())),
+            max_lines,
+        }
+    }
+
+    /// Start collecting the piped stdout/stderr of the child into the buffer.
+    pub(crate) fn capture(&self, child: &mut Child) {
+        if let Some(stdout) = child.stdout.take() {
+            self.spawn_reader(stdout);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            self.spawn_reader(stderr);
+        }
+    }
+
+    fn spawn_reader(&self, reader: impl AsyncRead + Unpin + Send + 'static) {
+        let buffer = self.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                buffer.push(line);
+            }
+        });
+    }
+
+    fn push(&self, line: String) {
+        if self.max_lines == 0 {
+            return;
+        }
+
+        let mut lines = self.lines.lock().expect("log buffer lock poisoned");
+        if lines.len() == self.max_lines {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    pub(crate) fn lines(&self) -> Vec<String> {
+        let lines = self.lines.lock().expect("log buffer lock poisoned");
+        lines.iter().cloned().collect()
+    }
+
+    /// The last `n` captured lines.
+    pub(crate) fn tail(&self, n: usize) -> Vec<String> {
+        let lines = self.lines.lock().expect("log buffer lock poisoned");
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+
+    /// Captured lines logged at ERROR level.
+    pub(crate) fn error_lines(&self) -> Vec<String> {
+        let lines = self.lines.lock().expect("log buffer lock poisoned");
+        lines
+            .iter()
+            .filter(|line| line.split_whitespace().any(|word| word == "ERROR"))
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn clear(&self) {
+        self.lines.lock().expect("log buffer lock poisoned").clear();
+    }
+}
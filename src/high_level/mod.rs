@@ -1,4 +1,8 @@
-use std::net::SocketAddrV4;
+use std::collections::BTreeSet;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::Mutex;
 use std::time::Duration;
 use std::{fs::File, net::Ipv4Addr};
 
@@ -6,16 +10,68 @@ use fs2::FileExt;
 use tempfile::TempDir;
 use tokio::net::TcpListener;
 use tokio::process::Child;
-use tracing::info;
+use tracing::{info, warn};
 
+#[cfg(feature = "near-api")]
+mod api;
 pub mod config;
-pub use config::{GenesisAccount, SandboxConfig, SandboxConfigError};
+mod logs;
+#[cfg(unix)]
+mod rlimit;
+mod rpc;
+#[cfg(feature = "workspaces")]
+mod workspaces;
+pub use config::{
+    GenesisAccount, GenesisContract, GenesisEconomics, GenesisParams, KeyFile, LogOutput,
+    ReadyPollHook, SandboxConfig, SandboxConfigBuilder, SandboxConfigError,
+};
+pub use rpc::{
+    fetch_account_records, AccessKeyInfo, AccessKeyPermission, AccountEdit, BlockSummary, RpcError,
+    StateRecord, TxOutcome,
+};
+
+use logs::LogBuffer;
 
 use crate::SandboxError;
 
 // Must be an IP address as `neard` expects socket address for network address.
 const DEFAULT_RPC_HOST: &str = "127.0.0.1";
 
+// How many times in a row the same non-connection error may occur while waiting for the
+// sandbox to become ready before giving up.
+const MAX_REPEATED_READY_ERRORS: u32 = 5;
+
+// How long the sandbox may take to answer `/status` unless configured otherwise.
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Readiness is polled about this many times within the startup timeout, within the bounds below.
+const READY_POLL_ATTEMPTS: u32 = 20;
+const MIN_READY_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_READY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// How many of the last captured log lines are included in a startup timeout error.
+const TIMEOUT_LOG_LINES: usize = 50;
+
+// How long `neard init` may take unless configured otherwise.
+const DEFAULT_INIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+// How long `Drop` waits for the killed sandbox process to be reaped.
+const KILL_WAIT_ATTEMPTS: u32 = 50;
+const KILL_WAIT_INTERVAL: Duration = Duration::from_millis(10);
+
+// How long `Sandbox::stop` waits for neard to exit after SIGTERM unless configured otherwise.
+const DEFAULT_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+// How many times binding or locking a port is attempted before giving up.
+const PORT_BIND_ATTEMPTS: u32 = 5;
+const PORT_BIND_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+// How long to wait for the occupant of a configured port to answer `/status`.
+const NEAR_NODE_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// First neard release reloading its dynamic config on SIGHUP.
+const MIN_CONFIG_RELOAD_VERSION: (u64, u64) = (1, 31);
+
 #[derive(thiserror::Error, Debug)]
 pub enum TcpError {
     #[error("Error while binding listener to a port {0}: {1}")]
@@ -26,66 +82,366 @@ pub enum TcpError {
 
     #[error("Error while locking port file: {0}")]
     LockingError(std::io::Error),
+
+    #[error("Port {0} is occupied by a responding NEAR node, most likely a stale sandbox that is still running")]
+    OccupiedByNearNodeError(u16),
+
+    #[error("Out of ports or file descriptors: {0}. Widen the ephemeral port range or close unused sockets")]
+    PortsExhaustedError(std::io::Error),
+}
+
+fn bind_error(port: u16, err: std::io::Error) -> TcpError {
+    if is_out_of_ports(&err) {
+        TcpError::PortsExhaustedError(err)
+    } else {
+        TcpError::BindError(port, err)
+    }
+}
+
+/// Whether a bind failed because there are no ports or descriptors left, rather than
+/// because of the port itself.
+fn is_out_of_ports(err: &std::io::Error) -> bool {
+    if err.kind() == std::io::ErrorKind::AddrNotAvailable {
+        return true;
+    }
+
+    #[cfg(unix)]
+    {
+        if matches!(err.raw_os_error(), Some(libc::EMFILE) | Some(libc::ENFILE)) {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Ports held by the sandboxes of this process, to tell a collision between them apart from a
+/// port taken by another process.
+static ACTIVE_PORTS: Mutex<BTreeSet<u16>> = Mutex::new(BTreeSet::new());
+
+/// Registration of a port in [`ACTIVE_PORTS`], removed once dropped.
+struct ActivePort(u16);
+
+impl ActivePort {
+    /// Returns `None` if the port is already registered by another sandbox.
+    fn register(port: u16) -> Option<Self> {
+        let mut ports = ACTIVE_PORTS.lock().expect("active ports lock poisoned");
+        ports.insert(port).then_some(Self(port))
+    }
+}
+
+impl Drop for ActivePort {
+    fn drop(&mut self) {
+        if let Ok(mut ports) = ACTIVE_PORTS.lock() {
+            ports.remove(&self.0);
+        }
+    }
+}
+
+/// Whether the given neard version reloads its config on SIGHUP. Versions that aren't a
+/// `major.minor[.patch]` release, like custom builds, are assumed to.
+fn supports_config_reload(version: &str) -> bool {
+    let mut parts = version.split('.').map(str::parse::<u64>);
+    match (parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor))) => (major, minor) >= MIN_CONFIG_RELOAD_VERSION,
+        _ => true,
+    }
 }
 
 fn rpc_socket(port: u16) -> String {
     format!("{DEFAULT_RPC_HOST}:{}", port)
 }
 
-/// Request an unused port from the OS.
-async fn pick_unused_port() -> Result<u16, SandboxError> {
-    // Port 0 means the OS gives us an unused port
+/// Socket address neard binds to, on `host` if configured.
+fn bind_socket(host: Option<IpAddr>, port: u16) -> String {
+    match host {
+        Some(host) => SocketAddr::new(host, port).to_string(),
+        None => rpc_socket(port),
+    }
+}
+
+/// URL the RPC bound on `host` is reachable at from this process. An unspecified address,
+/// like `0.0.0.0`, is reached through the loopback address.
+fn rpc_url(host: Option<IpAddr>, port: u16) -> String {
+    let host = match host {
+        Some(IpAddr::V4(host)) if host.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        Some(IpAddr::V6(host)) if host.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        Some(host) => host,
+        None => return format!("http://{}", rpc_socket(port)),
+    };
+    format!("http://{}", SocketAddr::new(host, port))
+}
+
+/// Address a port is reserved on before neard binds it: the configured `host`, so a port free
+/// on the loopback address but taken on `host` isn't handed out, localhost otherwise.
+fn reservation_addr(host: Option<IpAddr>, port: u16) -> SocketAddr {
     // Important to use localhost as using 0.0.0.0 leads to users getting brief firewall popups to
     // allow inbound connections on MacOS.
-    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
+    SocketAddr::new(host.unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST)), port)
+}
+
+/// Request an unused port on `host` from the OS, returning the listener bound to it.
+async fn pick_unused_port(host: Option<IpAddr>) -> Result<(u16, TcpListener), SandboxError> {
+    // Port 0 means the OS gives us an unused port
+    let addr = reservation_addr(host, 0);
     let listener = TcpListener::bind(addr)
         .await
-        .map_err(|e| TcpError::BindError(addr.port(), e))?;
+        .map_err(|e| bind_error(addr.port(), e))?;
     let port = listener
         .local_addr()
         .map_err(TcpError::LocalAddrError)?
         .port();
-    Ok(port)
+    Ok((port, listener))
+}
+
+fn port_lock_path(port: u16) -> PathBuf {
+    std::env::temp_dir().join(format!("near-sandbox-port{}.lock", port))
+}
+
+/// Open the lock file of `port` without truncating it, as truncating a file locked by another
+/// process fails on Windows.
+fn open_port_lock(port: u16) -> Result<File, TcpError> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(port_lock_path(port))
+        .map_err(TcpError::LockingError)
+}
+
+/// Open and lock the lock file of `port`, returning `None` if another process holds the lock.
+fn lock_port(port: u16) -> Result<Option<File>, TcpError> {
+    loop {
+        let lock = open_port_lock(port)?;
+        match lock.try_lock_exclusive() {
+            Ok(()) => {}
+            Err(err) if err.raw_os_error() == fs2::lock_contended_error().raw_os_error() => {
+                return Ok(None)
+            }
+            Err(err) => return Err(TcpError::LockingError(err)),
+        }
+
+        // The previous holder removes the file before unlocking it, so the lock may be on a
+        // removed file while another process locks a new one at the same path. Retry with the
+        // new file in that case.
+        if is_port_lock_file(port, &lock)? {
+            return Ok(Some(lock));
+        }
+    }
+}
+
+/// Whether `lock` is still the lock file of `port`, rather than one removed in the meantime.
+#[cfg(unix)]
+fn is_port_lock_file(port: u16, lock: &File) -> Result<bool, TcpError> {
+    use std::os::unix::fs::MetadataExt;
+
+    let locked = lock.metadata().map_err(TcpError::LockingError)?;
+    match std::fs::metadata(port_lock_path(port)) {
+        Ok(current) => Ok(locked.dev() == current.dev() && locked.ino() == current.ino()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(TcpError::LockingError(err)),
+    }
+}
+
+/// Windows doesn't remove a file that another process has open, see [`release_port_lock`], so
+/// a locked file is always the current one.
+#[cfg(not(unix))]
+fn is_port_lock_file(_port: u16, _lock: &File) -> Result<bool, TcpError> {
+    Ok(true)
+}
+
+/// Remove the lock file of `port` while still holding the lock, then release it, so lock files
+/// don't pile up in the temp dir. [`lock_port`] detects a lock taken on the removed file.
+#[cfg(not(windows))]
+fn release_port_lock(port: u16, lock: File) {
+    if let Err(err) = std::fs::remove_file(port_lock_path(port)) {
+        warn!(target: "sandbox", "Failed to remove lock file of port {}: {}", port, err);
+    }
+    drop(lock);
+}
+
+/// Release the lock of `port`, then remove its lock file. Windows keeps a file removed while
+/// open around until it's closed and fails to open it meanwhile, so the lock has to go first.
+/// The removal fails if another process opened the file in between, which is fine as it's
+/// reused.
+#[cfg(windows)]
+fn release_port_lock(port: u16, lock: File) {
+    drop(lock);
+    if let Err(err) = std::fs::remove_file(port_lock_path(port)) {
+        tracing::debug!(target: "sandbox", "Lock file of port {} is kept: {}", port, err);
+    }
+}
+
+/// Port reserved for a sandbox, from the moment it's picked until neard binds it.
+struct ReservedPort {
+    port: u16,
+    lock: File,
+    active: ActivePort,
+    /// Keeps other processes from binding the port until it's dropped right before neard is
+    /// spawned.
+    listener: TcpListener,
 }
 
 /// Acquire an unused port and lock it for the duration until the sandbox server has
 /// been started.
-async fn acquire_unused_port() -> Result<(u16, File), SandboxError> {
+async fn acquire_unused_port(host: Option<IpAddr>) -> Result<ReservedPort, SandboxError> {
+    acquire_picked_port(|| pick_unused_port(host)).await
+}
+
+/// Acquire a port handed out by `pick`, picking another one as long as the picked port is
+/// already used by another sandbox.
+async fn acquire_picked_port<F, Fut>(mut pick: F) -> Result<ReservedPort, SandboxError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<(u16, TcpListener), SandboxError>>,
+{
     loop {
-        let port = pick_unused_port().await?;
-        let lockpath = std::env::temp_dir().join(format!("near-sandbox-port{}.lock", port));
-        let lockfile = File::create(lockpath).map_err(TcpError::LockingError)?;
-        if lockfile.try_lock_exclusive().is_ok() {
-            break Ok((port, lockfile));
+        let (port, listener) = pick().await?;
+        if let Some(reserved) = reserve_picked_port(port, listener)? {
+            return Ok(reserved);
         }
+        // Taken by another sandbox in the meantime, try another one right away.
     }
 }
 
+/// Returns `None` if the picked port is already used by another sandbox.
+fn reserve_picked_port(
+    port: u16,
+    listener: TcpListener,
+) -> Result<Option<ReservedPort>, SandboxError> {
+    let Some(active) = ActivePort::register(port) else {
+        return Ok(None);
+    };
+    let Some(lock) = lock_port(port)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(ReservedPort {
+        port,
+        lock,
+        active,
+        listener,
+    }))
+}
+
 /// Try to acquire a specific port and lock it.
-/// Returns the port and lock file if successful.
-async fn try_acquire_specific_port(port: u16) -> Result<(u16, File), SandboxError> {
-    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, port);
-    let listener = TcpListener::bind(addr)
-        .await
-        .map_err(|e| TcpError::BindError(addr.port(), e))?;
-    let port = listener
-        .local_addr()
-        .map_err(TcpError::LocalAddrError)?
-        .port();
+///
+/// A port that's in use is retried a few times, as it may still be held by a sandbox that just
+/// stopped.
+async fn try_acquire_specific_port(
+    host: Option<IpAddr>,
+    port: u16,
+) -> Result<ReservedPort, SandboxError> {
+    let active = ActivePort::register(port).ok_or(SandboxError::PortAlreadyInUse { port })?;
+    let addr = reservation_addr(host, port);
+    let mut attempt = 1;
+    let listener = loop {
+        match TcpListener::bind(addr).await {
+            Ok(listener) => break listener,
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                if is_near_node(host, port).await {
+                    return Err(TcpError::OccupiedByNearNodeError(port).into());
+                }
+                if attempt >= PORT_BIND_ATTEMPTS {
+                    return Err(bind_error(port, e).into());
+                }
+                attempt += 1;
+                tokio::time::sleep(PORT_BIND_RETRY_INTERVAL).await;
+            }
+            Err(e) => return Err(bind_error(port, e).into()),
+        }
+    };
+
+    let lock =
+        lock_port(port)?.ok_or_else(|| TcpError::LockingError(fs2::lock_contended_error()))?;
 
-    let lockpath = std::env::temp_dir().join(format!("near-sandbox-port{}.lock", port));
-    let lockfile = File::create(&lockpath).map_err(TcpError::LockingError)?;
-    lockfile
-        .try_lock_exclusive()
-        .map_err(TcpError::LockingError)?;
+    Ok(ReservedPort {
+        port,
+        lock,
+        active,
+        listener,
+    })
+}
+
+/// Wait until `port` can be bound again, e.g. after the sandbox holding it exited.
+async fn wait_for_port_release(host: Option<IpAddr>, port: u16) -> Result<(), SandboxError> {
+    let addr = reservation_addr(host, port);
+    let mut attempt = 1;
+    loop {
+        match TcpListener::bind(addr).await {
+            Ok(_) => return Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && attempt < PORT_BIND_ATTEMPTS => {
+                attempt += 1;
+                tokio::time::sleep(PORT_BIND_RETRY_INTERVAL).await;
+            }
+            Err(e) => return Err(bind_error(port, e).into()),
+        }
+    }
+}
+
+/// neard version from a `/status` response, e.g. `2.6.3`.
+async fn reported_version(response: reqwest::Response) -> Option<String> {
+    let status = response.json::<serde_json::Value>().await.ok();
+    let version = status
+        .as_ref()
+        .and_then(|status| status["version"]["version"].as_str())
+        .map(str::to_string);
+    if version.is_none() {
+        warn!(target: "sandbox", "Sandbox didn't report its version in /status");
+    }
+
+    version
+}
 
-    Ok((port, lockfile))
+/// Whether the occupant of `port` answers `/status` like a NEAR node does.
+async fn is_near_node(host: Option<IpAddr>, port: u16) -> bool {
+    let response = reqwest::Client::new()
+        .get(format!("{}/status", rpc_url(host, port)))
+        .timeout(NEAR_NODE_PROBE_TIMEOUT)
+        .send()
+        .await;
+    let Ok(response) = response else {
+        return false;
+    };
+
+    response
+        .json::<serde_json::Value>()
+        .await
+        .is_ok_and(|status| status.get("chain_id").is_some() && status.get("version").is_some())
 }
 
-async fn acquire_or_lock_port(configured_port: Option<u16>) -> Result<(u16, File), SandboxError> {
+async fn acquire_or_lock_port(
+    host: Option<IpAddr>,
+    configured_port: Option<u16>,
+) -> Result<ReservedPort, SandboxError> {
     match configured_port {
-        Some(port) => try_acquire_specific_port(port).await,
-        None => acquire_unused_port().await,
+        Some(port) => try_acquire_specific_port(host, port).await,
+        None => acquire_unused_port(host).await,
+    }
+}
+
+/// Home directory of a [`Sandbox`].
+#[derive(Debug)]
+pub enum HomeDir {
+    /// Temporary directory, removed once the sandbox is dropped
+    Temp(TempDir),
+    /// Directory set by [`SandboxConfig::home_dir`], kept once the sandbox is dropped
+    Persistent(PathBuf),
+}
+
+impl HomeDir {
+    /// Path of the home directory
+    pub fn path(&self) -> &Path {
+        match self {
+            HomeDir::Temp(dir) => dir.path(),
+            HomeDir::Persistent(path) => path,
+        }
+    }
+}
+
+impl AsRef<Path> for HomeDir {
+    fn as_ref(&self) -> &Path {
+        self.path()
     }
 }
 
@@ -93,17 +449,36 @@ async fn acquire_or_lock_port(configured_port: Option<u16>) -> Result<(u16, File
 ///
 /// All the [examples](https://github.com/near/near-api-rs/tree/main/examples) are using Sandbox implementation.
 ///
+/// Requires a tokio runtime, either multi-thread or current-thread (the default of
+/// `#[tokio::test]`). Dropping the sandbox doesn't depend on the runtime still running.
+///
 /// This is work-in-progress and not all the features are supported yet.
 pub struct Sandbox {
-    /// Home directory for sandbox instance. Will be cleaned up once Sandbox is dropped
-    pub home_dir: TempDir,
-    /// URL that can be used to access RPC. In format of `http://127.0.0.1:{port}`
+    /// Home directory for sandbox instance. Cleaned up once Sandbox is dropped, unless it's
+    /// a persistent one set by [`SandboxConfig::home_dir`]
+    pub home_dir: HomeDir,
+    /// URL that can be used to access RPC. In format of `http://127.0.0.1:{port}`, or on
+    /// [`SandboxConfig::rpc_host`] if it's set to a specific address
     pub rpc_addr: String,
-    /// File lock preventing other processes from using the same RPC port until this sandbox is started
-    pub rpc_port_lock: File,
-    /// File lock preventing other processes from using the same network port until this sandbox is started
-    pub net_port_lock: File,
+    /// File lock preventing other processes from using the same RPC port until this sandbox is started.
+    /// `None` once released, see [`SandboxConfig::release_port_locks_on_ready`].
+    pub rpc_port_lock: Option<File>,
+    /// File lock preventing other processes from using the same network port until this sandbox is started.
+    /// `None` once released, see [`SandboxConfig::release_port_locks_on_ready`].
+    pub net_port_lock: Option<File>,
+    rpc_port: u16,
+    net_port: u16,
     process: Child,
+    logs: LogBuffer,
+    rpc_client: reqwest::Client,
+    network_name: String,
+    version: String,
+    bin_path: PathBuf,
+    config: SandboxConfig,
+    /// Metrics port together with its file lock, `None` if not configured or released
+    metrics_port_lock: Option<(u16, File)>,
+    active_ports: Vec<ActivePort>,
+    genesis_accounts: Vec<GenesisAccount>,
 }
 
 impl Sandbox {
@@ -164,17 +539,17 @@ impl Sandbox {
     /// use serde_json::json;
     ///
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut cfg = SandboxConfig::default();
-    /// cfg.rpc_port = Some(3030);
-    /// cfg.additional_genesis = Some(json!({ "epoch_length": 200 }));
-    /// cfg.additional_accounts = vec![
-    ///     GenesisAccount {
+    /// let cfg = SandboxConfig::builder()
+    ///     .rpc_port(3030)
+    ///     .additional_genesis(json!({ "epoch_length": 200 }))
+    ///     .add_account(GenesisAccount {
     ///         account_id: "bob.near".parse().unwrap(),
     ///         public_key: "ed25519:...".to_string(),
     ///         private_key: "ed25519:...".to_string(),
     ///         balance: 10_000u128 * 10u128.pow(24), // 10000 NEAR
-    ///     },
-    /// ];
+    ///         ..Default::default()
+    ///     })
+    ///     .build();
     ///
     /// let sandbox = Sandbox::start_sandbox_with_config(cfg).await?;
     /// println!("Custom sandbox running at {}", sandbox.rpc_addr);
@@ -186,6 +561,31 @@ impl Sandbox {
             .await
     }
 
+    /// Start a new sandbox with the default version, configured by a closure over a
+    /// [`SandboxConfigBuilder`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox_utils::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_with(|cfg| {
+    ///     cfg.rpc_port(3030).epoch_length(200);
+    /// })
+    /// .await?;
+    /// println!("Custom sandbox running at {}", sandbox.rpc_addr);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn start_with(
+        configure: impl FnOnce(&mut SandboxConfigBuilder),
+    ) -> Result<Self, SandboxError> {
+        let mut builder = SandboxConfig::builder();
+        configure(&mut builder);
+        Self::start_sandbox_with_config(builder.build()).await
+    }
+
     /// Start a new sandbox with a custom configuration and specific near-sandbox-utils version.
     ///
     /// # Arguments
@@ -199,17 +599,17 @@ impl Sandbox {
     /// use serde_json::json;
     ///
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut cfg = SandboxConfig::default();
-    /// cfg.rpc_port = Some(3030);
-    /// cfg.additional_genesis = Some(json!({ "epoch_length": 200 }));
-    /// cfg.additional_accounts = vec![
-    ///     GenesisAccount {
+    /// let cfg = SandboxConfig::builder()
+    ///     .rpc_port(3030)
+    ///     .additional_genesis(json!({ "epoch_length": 200 }))
+    ///     .add_account(GenesisAccount {
     ///         account_id: "bob.near".parse().unwrap(),
     ///         public_key: "ed25519:...".to_string(),
     ///         private_key: "ed25519:...".to_string(),
     ///         balance: 10_000u128 * 10u128.pow(24), // 10000 NEAR
-    ///     },
-    /// ];
+    ///         ..Default::default()
+    ///     })
+    ///     .build();
     ///
     /// let sandbox = Sandbox::start_sandbox_with_config_and_version(cfg, "2.6.3").await?;
     /// println!("Custom sandbox running at {}", sandbox.rpc_addr);
@@ -220,21 +620,389 @@ impl Sandbox {
         config: SandboxConfig,
         version: &str,
     ) -> Result<Self, SandboxError> {
+        // Explicitly set fields take precedence over the environment.
+        let config = config.with_env()?;
+        config.validate()?;
+        let genesis_accounts = config::genesis_accounts(&config)?;
         suppress_sandbox_logs_if_required();
-        let home_dir = Self::init_home_dir_with_version(version).await?;
+        #[cfg(unix)]
+        if config.raise_open_files_limit.unwrap_or(true) {
+            rlimit::raise_open_files_limit(config::max_open_files(&config))?;
+        }
+        // Resolve the binary once, so init and run are guaranteed to use the same one.
+        let bin_path = match &config.binary_path {
+            Some(path) => {
+                crate::check_binary(path)?;
+                path.clone()
+            }
+            None => crate::ensure_sandbox_bin_once(version).await?,
+        };
+        let (home_dir, fresh) = Self::init_home_dir(&bin_path, &config).await?;
+
+        let rpc = acquire_or_lock_port(config.rpc_host, config.rpc_port).await?;
+        let net = acquire_or_lock_port(config.net_host, config.net_port).await?;
+        let (rpc_port, net_port) = (rpc.port, net.port);
+        let metrics = match config.metrics_port {
+            // Prometheus is served on the RPC host.
+            Some(port) => Some(try_acquire_specific_port(config.rpc_host, port).await?),
+            None => None,
+        };
+
+        // A resumed home dir already has its chain state, changing the genesis would break it.
+        if fresh {
+            config::set_sandbox_configs_with_config(&home_dir, &config)?;
+            config::set_sandbox_genesis_with_config(&home_dir, &config)?;
+        }
+        config::set_sandbox_validator_with_config(&home_dir, &config)?;
+
+        let logs = LogBuffer::new(
+            config
+                .max_log_lines
+                .unwrap_or(config::DEFAULT_MAX_LOG_LINES),
+        );
+        // Hand the ports over to neard as late as possible.
+        drop((rpc.listener, net.listener));
+        let mut active_ports = vec![rpc.active, net.active];
+        let metrics_port_lock = metrics.map(|metrics| {
+            active_ports.push(metrics.active);
+            (metrics.port, metrics.lock)
+        });
+        let mut child = Self::spawn_run(
+            &bin_path,
+            home_dir.path(),
+            rpc_port,
+            net_port,
+            &config,
+            &logs,
+        )?;
+
+        let rpc_addr = rpc_url(config.rpc_host, rpc_port);
+
+        let running_version =
+            match Self::wait_until_ready(&rpc_addr, &mut child, &config, &logs).await {
+                Ok(running_version) => running_version,
+                Err(err) => {
+                    // Don't leave a half-started neard behind.
+                    let _ = child.kill().await;
+                    return Err(err);
+                }
+            };
+
+        // neard holds the sockets by now, the locks aren't needed anymore.
+        let (rpc_port_lock, net_port_lock, metrics_port_lock) =
+            if config.release_port_locks_on_ready {
+                release_port_lock(rpc_port, rpc.lock);
+                release_port_lock(net_port, net.lock);
+                if let Some((port, lock)) = metrics_port_lock {
+                    release_port_lock(port, lock);
+                }
+                (None, None, None)
+            } else {
+                (Some(rpc.lock), Some(net.lock), metrics_port_lock)
+            };
+
+        let network_name = config
+            .network_name
+            .clone()
+            .or_else(|| config.genesis.chain_id.clone())
+            .unwrap_or_else(|| config::DEFAULT_NETWORK_NAME.to_string());
+
+        Ok(Self {
+            home_dir,
+            rpc_addr,
+            rpc_port_lock,
+            net_port_lock,
+            rpc_port,
+            net_port,
+            process: child,
+            logs,
+            rpc_client: reqwest::Client::new(),
+            network_name,
+            version: running_version.unwrap_or_else(|| version.to_string()),
+            bin_path,
+            config,
+            metrics_port_lock,
+            active_ports,
+            genesis_accounts,
+        })
+    }
+
+    /// Start `n` sandboxes concurrently with the same configuration and the default version.
+    ///
+    /// The binary is resolved once and shared by all instances, each of which gets its own
+    /// home dir and ports. If any instance fails to start, the ones already running are stopped.
+    /// `config` must not set any port or `home_dir` when `n` is greater than 1.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox_utils::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandboxes = Sandbox::start_many(4, SandboxConfig::default()).await?;
+    /// for sandbox in &sandboxes {
+    ///     println!("Sandbox running at {}", sandbox.rpc_addr);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn start_many(
+        n: usize,
+        mut config: SandboxConfig,
+    ) -> Result<Vec<Self>, SandboxError> {
+        if n > 1 {
+            for (name, is_set) in [
+                ("rpc_port", config.rpc_port.is_some()),
+                ("net_port", config.net_port.is_some()),
+                ("metrics_port", config.metrics_port.is_some()),
+                ("home_dir", config.home_dir.is_some()),
+            ] {
+                if is_set {
+                    return Err(SandboxConfigError::InvalidConfigError(format!(
+                        "{name} can't be shared by {n} sandboxes"
+                    ))
+                    .into());
+                }
+            }
+        }
+
+        let version = crate::DEFAULT_NEAR_SANDBOX_VERSION;
+        if config.binary_path.is_none() {
+            config.binary_path = Some(crate::ensure_sandbox_bin_once(version).await?);
+        }
+
+        // Dropping the instances that already started kills them when another one fails.
+        futures::future::try_join_all(
+            (0..n).map(|_| Self::start_sandbox_with_config_and_version(config.clone(), version)),
+        )
+        .await
+    }
+
+    /// Port the RPC is bound to.
+    pub fn rpc_port(&self) -> u16 {
+        self.rpc_port
+    }
+
+    /// Port the network is bound to, as used in `boot_nodes` of other nodes.
+    pub fn net_port(&self) -> u16 {
+        self.net_port
+    }
+
+    /// URL neard serves its Prometheus metrics on, under `/metrics`, if
+    /// [`SandboxConfig::metrics_port`] is set. Metrics are served on [`Sandbox::rpc_addr`]
+    /// otherwise.
+    pub fn metrics_addr(&self) -> Option<String> {
+        self.config
+            .metrics_port
+            .map(|port| rpc_url(self.config.rpc_host, port))
+    }
+
+    /// URL of the RPC, same as [`Sandbox::rpc_addr`].
+    pub fn rpc_url(&self) -> &str {
+        &self.rpc_addr
+    }
+
+    /// Accounts added to the genesis, the default one first unless
+    /// [`SandboxConfig::include_default_account`] is `false`.
+    pub fn genesis_accounts(&self) -> &[GenesisAccount] {
+        &self.genesis_accounts
+    }
+
+    /// Path of the key file, with the `account_id`, `public_key` and `private_key`, written
+    /// into the home dir for a genesis account.
+    pub fn key_file_path(&self, account_id: &str) -> PathBuf {
+        config::account_key_path(self.home_dir.path(), account_id)
+    }
+
+    /// Paths of the RPC and network port lock files, in that order.
+    ///
+    /// The lock files live in the temp dir (`std::env::temp_dir()`) and are shared by all
+    /// processes using this crate, so external tools can lock them to avoid handing out the
+    /// same ports. They're removed once the locks are released.
+    pub fn lock_paths(&self) -> (PathBuf, PathBuf) {
+        (port_lock_path(self.rpc_port), port_lock_path(self.net_port))
+    }
+
+    /// Path of the sandbox binary this sandbox was started with.
+    pub fn binary_path(&self) -> &Path {
+        &self.bin_path
+    }
+
+    /// Version of the running neard, as reported by its `/status`. Reflects the binary actually
+    /// used, e.g. one set through [`SandboxConfig::binary_path`], rather than the requested
+    /// version.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Returns the exit status of the sandbox process if it has already exited, or `None` if
+    /// it's still running. Doesn't kill or otherwise affect the process.
+    pub fn exit_status(&mut self) -> Result<Option<ExitStatus>, SandboxError> {
+        self.process.try_wait().map_err(SandboxError::RuntimeError)
+    }
+
+    /// Name of the network, as set by [`SandboxConfig::network_name`] or derived from the chain id.
+    pub fn network_name(&self) -> &str {
+        &self.network_name
+    }
+
+    /// Number of accounts in the current state of the sandbox.
+    ///
+    /// neard has no RPC to count accounts, so this dumps the state with the offline
+    /// `neard view-state dump-state` state viewer, which opens the store read-only next to the
+    /// running node. The dump takes time proportional to the state size, so this is meant for
+    /// verifying bulk-account setups rather than for frequent polling.
+    pub async fn account_count(&self) -> Result<usize, SandboxError> {
+        let dump_dir = tempfile::tempdir().map_err(SandboxError::FileError)?;
+        let dump_file = dump_dir.path().join("genesis.json");
+
+        let output = crate::sandbox_command(&self.bin_path)
+            .arg("--home")
+            .arg(self.home_dir.path())
+            .args(["view-state", "dump-state", "--file"])
+            .arg(&dump_file)
+            .output()
+            .await
+            .map_err(SandboxError::RuntimeError)?;
+        if !output.status.success() {
+            return Err(SandboxError::RuntimeError(std::io::Error::other(format!(
+                "neard view-state dump-state failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            ))));
+        }
 
-        let (rpc_port, rpc_port_lock) = acquire_or_lock_port(config.rpc_port).await?;
-        let (net_port, net_port_lock) = acquire_or_lock_port(config.net_port).await?;
+        let dump = File::open(&dump_file).map_err(SandboxError::FileError)?;
+        let genesis: serde_json::Value = serde_json::from_reader(std::io::BufReader::new(dump))
+            .map_err(config::SandboxConfigError::from)?;
+        let count = genesis["records"].as_array().map_or(0, |records| {
+            records
+                .iter()
+                .filter(|record| record.get("Account").is_some())
+                .count()
+        });
 
-        let rpc_addr = rpc_socket(rpc_port);
-        let net_addr = rpc_socket(net_port);
+        Ok(count)
+    }
+
+    /// Merges `patch` into the `config.json` of the running sandbox and signals neard to reload
+    /// it with `SIGHUP`, without restarting the node.
+    ///
+    /// Only the keys neard treats as dynamic, such as `expected_shutdown`, take effect right
+    /// away, the rest is picked up on the next restart. Requires a unix platform and neard 1.31
+    /// or newer.
+    pub async fn reload_config(&self, patch: serde_json::Value) -> Result<(), SandboxError> {
+        if !supports_config_reload(&self.version) {
+            let (major, minor) = MIN_CONFIG_RELOAD_VERSION;
+            return Err(SandboxError::UnsupportedVersionError(format!(
+                "sandbox {} can't reload its config, {major}.{minor} or newer is required",
+                self.version
+            )));
+        }
+
+        // Checked before writing anything, so the config isn't left patched on disk when neard
+        // can't be told to reload it.
+        #[cfg(not(unix))]
+        {
+            let _ = patch;
+            Err(SandboxError::UnsupportedPlatformError(
+                "config reload relies on SIGHUP, which is only available on unix".to_string(),
+            ))
+        }
+
+        #[cfg(unix)]
+        {
+            config::overwrite(self.home_dir.path(), patch)?;
+            self.send_signal(libc::SIGHUP)
+        }
+    }
+
+    #[cfg(unix)]
+    fn send_signal(&self, signal: libc::c_int) -> Result<(), SandboxError> {
+        let pid = self.process.id().ok_or_else(|| {
+            SandboxError::RuntimeError(std::io::Error::other("sandbox process has exited"))
+        })?;
+        if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+            return Err(SandboxError::RuntimeError(std::io::Error::last_os_error()));
+        }
+
+        Ok(())
+    }
+
+    /// Gracefully shuts the sandbox down and removes its home directory.
+    ///
+    /// Sends SIGTERM and waits for neard to exit, so RocksDB is flushed and closed before the
+    /// home directory is removed. neard is killed if it doesn't exit within the grace period of
+    /// `NEAR_SANDBOX_SHUTDOWN_SECS`, 5 seconds by default. On platforms without SIGTERM it's
+    /// killed right away.
+    ///
+    /// Dropping the sandbox without calling `stop` kills neard instead.
+    pub async fn stop(mut self) -> Result<(), SandboxError> {
+        self.shutdown_process().await?;
+
+        // The process has been reaped, so `Drop` only removes the home directory.
+        Ok(())
+    }
+
+    /// Restarts neard on the same home directory and ports, without `neard init`, so it
+    /// recovers from the existing chain state.
+    ///
+    /// neard is shut down gracefully first, like in [`Sandbox::stop`], so RocksDB is closed
+    /// before the new process opens it, and its ports are retried until the OS releases them.
+    /// Returns once the restarted sandbox is ready.
+    #[doc(alias = "restart")]
+    pub async fn respawn(&mut self) -> Result<(), SandboxError> {
+        self.shutdown_process().await?;
+        let ports = [
+            (self.config.rpc_host, self.rpc_port),
+            (self.config.net_host, self.net_port),
+        ];
+        let metrics = self
+            .config
+            .metrics_port
+            .map(|port| (self.config.rpc_host, port));
+        for (host, port) in ports.iter().copied().chain(metrics) {
+            wait_for_port_release(host, port).await?;
+        }
+        self.process = Self::spawn_run(
+            &self.bin_path,
+            self.home_dir.path(),
+            self.rpc_port,
+            self.net_port,
+            &self.config,
+            &self.logs,
+        )?;
 
-        config::set_sandbox_configs_with_config(&home_dir, &config)?;
-        config::set_sandbox_genesis_with_config(&home_dir, &config)?;
+        match Self::wait_until_ready(&self.rpc_addr, &mut self.process, &self.config, &self.logs)
+            .await
+        {
+            Ok(running_version) => {
+                if let Some(running_version) = running_version {
+                    self.version = running_version;
+                }
+                Ok(())
+            }
+            Err(err) => {
+                self.kill_process();
+                Err(err)
+            }
+        }
+    }
 
+    /// Spawn `neard run` on `home_dir`, capturing its output into `logs` if configured.
+    fn spawn_run(
+        bin_path: &Path,
+        home_dir: &Path,
+        rpc_port: u16,
+        net_port: u16,
+        config: &SandboxConfig,
+        logs: &LogBuffer,
+    ) -> Result<Child, SandboxError> {
+        let rpc_addr = bind_socket(config.rpc_host, rpc_port);
+        let net_addr = bind_socket(config.net_host, net_port);
         let options = &[
             "--home",
-            home_dir.path().to_str().expect("home_dir is valid utf8"),
+            home_dir.to_str().expect("home_dir is valid utf8"),
             "run",
             "--rpc-addr",
             &rpc_addr,
@@ -242,52 +1010,274 @@ impl Sandbox {
             &net_addr,
         ];
 
-        let child = crate::run_with_options_with_version(options, version)?;
+        let mut command = crate::sandbox_command(bin_path);
+        command.args(options);
+        if let Some(backtrace) = &config.rust_backtrace {
+            command.env("RUST_BACKTRACE", backtrace);
+        }
+        match &config.log_output {
+            LogOutput::Inherit => {}
+            LogOutput::Captured => {
+                command.stdout(Stdio::piped()).stderr(Stdio::piped());
+            }
+            LogOutput::File(path) => {
+                let stdout = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(SandboxError::FileError)?;
+                let stderr = stdout.try_clone().map_err(SandboxError::FileError)?;
+                command.stdout(stdout).stderr(stderr);
+            }
+        }
+        let mut child = command.spawn().map_err(SandboxError::RuntimeError)?;
+        logs.capture(&mut child);
 
         info!(target: "sandbox", "Started up sandbox at localhost:{} with pid={:?}", rpc_port, child.id());
 
-        let rpc_addr = format!("http://{rpc_addr}");
+        Ok(child)
+    }
+
+    /// Send SIGTERM to neard and wait for it to exit, killing it after the grace period.
+    async fn shutdown_process(&mut self) -> Result<(), SandboxError> {
+        if self
+            .process
+            .try_wait()
+            .map_err(SandboxError::RuntimeError)?
+            .is_some()
+        {
+            return Ok(());
+        }
 
-        Self::wait_until_ready(&rpc_addr).await?;
+        let grace_period = self
+            .config
+            .shutdown_grace_period
+            .unwrap_or(DEFAULT_SHUTDOWN_GRACE_PERIOD);
 
-        Ok(Self {
-            home_dir,
-            rpc_addr,
-            rpc_port_lock,
-            net_port_lock,
-            process: child,
-        })
+        #[cfg(unix)]
+        self.send_signal(libc::SIGTERM)?;
+        #[cfg(not(unix))]
+        self.process
+            .start_kill()
+            .map_err(SandboxError::RuntimeError)?;
+
+        match tokio::time::timeout(grace_period, self.process.wait()).await {
+            Ok(status) => {
+                let status = status.map_err(SandboxError::RuntimeError)?;
+                info!(target: "sandbox", "Sandbox stopped: {:?}", status);
+            }
+            Err(_) => {
+                warn!(target: "sandbox", "Sandbox didn't stop within {:?}, killing it", grace_period);
+                self.process
+                    .kill()
+                    .await
+                    .map_err(SandboxError::RuntimeError)?;
+            }
+        }
+
+        Ok(())
     }
 
-    async fn init_home_dir_with_version(version: &str) -> Result<TempDir, SandboxError> {
-        let home_dir = tempfile::tempdir().map_err(SandboxError::FileError)?;
+    /// Copies the effective `config.json` and `genesis.json` of the sandbox into `dest`,
+    /// creating the directory if needed.
+    ///
+    /// Useful for turning an ad-hoc sandbox into a fixture that can be launched again.
+    pub fn export_config(&self, dest: impl AsRef<Path>) -> Result<(), SandboxError> {
+        let dest = dest.as_ref();
+        std::fs::create_dir_all(dest).map_err(SandboxError::FileError)?;
 
-        let output = crate::init_with_version(&home_dir, version)?
-            .wait_with_output()
-            .await
-            .map_err(SandboxError::RuntimeError)?;
-        info!(target: "sandbox", "sandbox init: {:?}", output);
+        for file in ["config.json", "genesis.json"] {
+            std::fs::copy(self.home_dir.path().join(file), dest.join(file))
+                .map_err(SandboxError::FileError)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the captured output of the sandbox process.
+    ///
+    /// Empty unless the sandbox was started with [`LogOutput::Captured`]. Only the last
+    /// [`SandboxConfig::max_log_lines`] lines are kept.
+    pub fn logs(&self) -> String {
+        self.logs.lines().join("\n")
+    }
+
+    /// Returns a snapshot of the captured output of the sandbox process, one entry per line.
+    ///
+    /// Same contents as [`Sandbox::logs`], without joining the lines.
+    pub fn log_lines(&self) -> impl Iterator<Item = String> {
+        self.logs.lines().into_iter()
+    }
+
+    /// Number of captured log lines at ERROR level.
+    pub fn error_log_count(&self) -> usize {
+        self.logs.error_lines().len()
+    }
+
+    /// Fails with [`SandboxError::LoggedErrors`] listing the captured ERROR lines, if any.
+    ///
+    /// Useful to fail a test when the node logged errors even though all RPC calls succeeded.
+    /// Requires [`LogOutput::Captured`], otherwise there's nothing to check.
+    pub fn assert_no_errors(&self) -> Result<(), SandboxError> {
+        let errors = self.logs.error_lines();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(SandboxError::LoggedErrors(errors))
+        }
+    }
+
+    /// Clears the captured output of the sandbox process.
+    pub fn clear_logs(&self) {
+        self.logs.clear();
+    }
+
+    /// Start a new sandbox with `wasm` deployed at genesis to a freshly generated and funded account.
+    ///
+    /// Returns the sandbox together with the generated [`GenesisAccount`], which holds the
+    /// account id and the keys needed to call the contract. Requires the `generate` feature.
+    ///
+    /// The account is returned as a [`GenesisAccount`] rather than as `AccountId` and
+    /// `SecretKey`, because those types come from near-api, which is an optional dependency, and
+    /// the signature shouldn't change with the enabled features. With the `near-api` feature,
+    /// `contract.account_id.parse::<AccountId>()` and `contract.private_key.parse::<SecretKey>()`
+    /// give the near-api types.
+    ///
+    /// # Arguments
+    /// * `wasm` - compiled code of the contract
+    /// * `version` - the version of the near-sandbox-utils to use
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox_utils::*;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let wasm = std::fs::read("target/near/contract.wasm")?;
+    /// let (sandbox, contract) = Sandbox::start_with_contract(&wasm, DEFAULT_NEAR_SANDBOX_VERSION).await?;
+    /// println!("{} deployed to {}", contract.account_id, sandbox.rpc_addr);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "generate")]
+    pub async fn start_with_contract(
+        wasm: &[u8],
+        version: &str,
+    ) -> Result<(Self, GenesisAccount), SandboxError> {
+        let account = GenesisAccount::generate_random();
+        let config = SandboxConfig {
+            additional_accounts: vec![account.clone()],
+            genesis_contracts: vec![GenesisContract {
+                account_id: account.account_id.clone(),
+                code: wasm.to_vec(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
 
-        Ok(home_dir)
+        let sandbox = Self::start_sandbox_with_config_and_version(config, version).await?;
+        Ok((sandbox, account))
     }
 
-    async fn wait_until_ready(rpc: &str) -> Result<(), SandboxError> {
-        let timeout_secs = match std::env::var("NEAR_RPC_TIMEOUT_SECS") {
-            Ok(secs) => secs
-                .parse::<u64>()
-                .expect("Failed to parse NEAR_RPC_TIMEOUT_SECS"),
-            Err(_) => 10,
+    /// Create the home directory and run `neard init` in it. Returns whether the directory was
+    /// freshly initialized, as opposed to a persistent one resumed from an earlier run.
+    async fn init_home_dir(
+        bin_path: &Path,
+        config: &SandboxConfig,
+    ) -> Result<(HomeDir, bool), SandboxError> {
+        let home_dir = if let Some(path) = &config.home_dir {
+            if path.join("genesis.json").exists() {
+                info!(target: "sandbox", "Resuming sandbox from {}", path.display());
+                return Ok((HomeDir::Persistent(path.clone()), false));
+            }
+            std::fs::create_dir_all(path).map_err(SandboxError::FileError)?;
+            HomeDir::Persistent(path.clone())
+        } else if config.in_memory_store {
+            HomeDir::Temp(in_memory_tempdir()?)
+        } else if let Some(path) = &config.store_path {
+            HomeDir::Temp(
+                tempfile::Builder::new()
+                    .prefix("near-sandbox")
+                    .tempdir_in(path)
+                    .map_err(SandboxError::FileError)?,
+            )
+        } else {
+            HomeDir::Temp(tempfile::tempdir().map_err(SandboxError::FileError)?)
         };
 
-        let mut interval = tokio::time::interval(Duration::from_millis(500));
-        for _ in 0..timeout_secs * 2 {
+        let timeout = config.init_timeout.unwrap_or(DEFAULT_INIT_TIMEOUT);
+
+        let mut child = crate::init_with_bin(&home_dir, bin_path)?;
+        let status = match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(status) => status.map_err(SandboxError::RuntimeError)?,
+            Err(_) => {
+                let _ = child.kill().await;
+                return Err(SandboxError::WaitTimeoutError(format!(
+                    "sandbox init didn't finish within {timeout:?}"
+                )));
+            }
+        };
+        info!(target: "sandbox", "sandbox init: {:?}", status);
+
+        Ok((home_dir, true))
+    }
+
+    /// Wait until the RPC answers `/status`, failing early if `process` exits in the meantime.
+    /// Returns the neard version reported in `/status`, if any.
+    async fn wait_until_ready(
+        rpc: &str,
+        process: &mut Child,
+        config: &SandboxConfig,
+        logs: &LogBuffer,
+    ) -> Result<Option<String>, SandboxError> {
+        let timeout = config.startup_timeout.unwrap_or(DEFAULT_STARTUP_TIMEOUT);
+        // Poll often enough that even short timeouts get several attempts.
+        let poll_interval =
+            (timeout / READY_POLL_ATTEMPTS).clamp(MIN_READY_POLL_INTERVAL, MAX_READY_POLL_INTERVAL);
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        // Connection errors are expected while neard is still starting up, anything else
+        // repeating several times in a row is most likely a misconfiguration and fails early.
+        let mut last_error: Option<String> = None;
+        let mut repeated_errors = 0;
+
+        let mut interval = tokio::time::interval(poll_interval);
+        let mut attempt = 0;
+        while tokio::time::Instant::now() < deadline {
             interval.tick().await;
-            let response = reqwest::get(format!("{}/status", rpc)).await;
-            if response.is_ok() {
-                return Ok(());
+            attempt += 1;
+            if let Some(on_poll) = &config.on_ready_poll {
+                on_poll.call(attempt);
+            }
+            if let Ok(Some(status)) = process.try_wait() {
+                return Err(SandboxError::ProcessExited {
+                    status,
+                    logs: logs.tail(TIMEOUT_LOG_LINES),
+                });
+            }
+            let err = match reqwest::get(format!("{}/status", rpc)).await {
+                Ok(response) => return Ok(reported_version(response).await),
+                Err(err) => err,
+            };
+
+            if err.is_connect() || err.is_timeout() {
+                last_error = None;
+                repeated_errors = 0;
+                continue;
+            }
+
+            let message = err.to_string();
+            if last_error.as_ref() == Some(&message) {
+                repeated_errors += 1;
+            } else {
+                last_error = Some(message);
+                repeated_errors = 1;
+            }
+            if repeated_errors >= MAX_REPEATED_READY_ERRORS {
+                return Err(SandboxError::RequestError(err));
             }
         }
-        Err(SandboxError::TimeoutError)
+        Err(SandboxError::TimeoutError(logs.tail(TIMEOUT_LOG_LINES)))
     }
 }
 
@@ -299,9 +1289,75 @@ impl Drop for Sandbox {
             self.process.id()
         );
 
-        self.process.start_kill().expect("failed to kill sandbox");
-        let _ = self.process.try_wait();
+        self.kill_process();
+
+        let locks = [
+            (self.rpc_port, self.rpc_port_lock.take()),
+            (self.net_port, self.net_port_lock.take()),
+        ];
+        let locks = IntoIterator::into_iter(locks)
+            .filter_map(|(port, lock)| Some((port, lock?)))
+            .chain(self.metrics_port_lock.take());
+        for (port, lock) in locks {
+            release_port_lock(port, lock);
+        }
+        // Only let other sandboxes of this process have the ports once the locks are gone.
+        self.active_ports.clear();
+    }
+}
+
+impl Sandbox {
+    /// Kill the sandbox process and wait briefly for it to exit, without relying on the runtime.
+    fn kill_process(&mut self) {
+        // Already exited, e.g. after `stop`, nothing to kill.
+        if let Ok(Some(_)) = self.process.try_wait() {
+            return;
+        }
+
+        // The sandbox may be dropped while the runtime is shutting down (e.g. at the end of a
+        // current-thread `#[tokio::test]`), so don't rely on tokio to reap the child and wait
+        // for it synchronously instead. SIGKILL makes this wait short.
+        if let Err(err) = self.process.start_kill() {
+            warn!(target: "sandbox", "Failed to kill sandbox: {}", err);
+        }
+        for _ in 0..KILL_WAIT_ATTEMPTS {
+            match self.process.try_wait() {
+                Ok(None) => std::thread::sleep(KILL_WAIT_INTERVAL),
+                Ok(Some(_)) | Err(_) => return,
+            }
+        }
+
+        // Still not exited, tokio takes over the child once it's dropped and reaps it on its
+        // own, as it's the only owner of the pid.
+        warn!(
+            target: "sandbox",
+            "Sandbox didn't exit within {:?} of being killed: pid={:?}",
+            KILL_WAIT_INTERVAL * KILL_WAIT_ATTEMPTS,
+            self.process.id()
+        );
+    }
+}
+
+/// Create a temporary directory on tmpfs, so the chain data never hits the disk.
+/// Falls back to the regular temporary directory where tmpfs isn't available.
+fn in_memory_tempdir() -> Result<TempDir, SandboxError> {
+    const SHM_DIR: &str = "/dev/shm";
+
+    if !cfg!(target_os = "linux") {
+        warn!(target: "sandbox", "In-memory store is only supported on Linux, using regular temp dir");
+    } else {
+        match tempfile::Builder::new()
+            .prefix("near-sandbox")
+            .tempdir_in(SHM_DIR)
+        {
+            Ok(dir) => return Ok(dir),
+            Err(err) => {
+                warn!(target: "sandbox", "Failed to create home dir in {}, using regular temp dir: {}", SHM_DIR, err)
+            }
+        }
     }
+
+    tempfile::tempdir().map_err(SandboxError::FileError)
 }
 
 /// Turn off neard-sandbox logs by default. Users can turn them back on with
@@ -320,3 +1376,110 @@ fn suppress_sandbox_logs_if_required() {
     // does nothing in this case, since nearcore seems to be overriding it somehow:
     std::env::set_var("NEAR_SANDBOX_LOG", "near=error,stats=error,network=error");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Port held by a listener closing every connection right away, standing in for another
+    /// process using it that isn't a NEAR node.
+    async fn take_port() -> (u16, tokio::task::JoinHandle<()>) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepting = tokio::spawn(async move {
+            loop {
+                let _ = listener.accept().await;
+            }
+        });
+        (port, accepting)
+    }
+
+    #[tokio::test]
+    async fn taken_specific_port_is_retried_before_failing() {
+        let (port, taken) = take_port().await;
+
+        let started = std::time::Instant::now();
+        let result = try_acquire_specific_port(None, port).await;
+        let elapsed = started.elapsed();
+        taken.abort();
+
+        match result {
+            Err(SandboxError::TcpError(TcpError::BindError(failed_port, _))) => {
+                assert_eq!(failed_port, port);
+            }
+            Err(err) => panic!("unexpected error: {err}"),
+            Ok(_) => panic!("acquired port {port} while it's taken"),
+        }
+        assert!(elapsed >= PORT_BIND_RETRY_INTERVAL * (PORT_BIND_ATTEMPTS - 1));
+        assert!(ActivePort::register(port).is_some());
+    }
+
+    #[tokio::test]
+    async fn picked_port_locked_by_another_process_is_skipped() {
+        // Reserved by another process, which hasn't spawned neard on it yet.
+        let (locked_port, listener) = pick_unused_port(None).await.unwrap();
+        let other_lock = lock_port(locked_port).unwrap().unwrap();
+
+        let mut first_pick = Some((locked_port, listener));
+        let mut picks = 0;
+        let reserved = acquire_picked_port(|| {
+            picks += 1;
+            let first_pick = first_pick.take();
+            async move {
+                match first_pick {
+                    Some(picked) => Ok(picked),
+                    None => pick_unused_port(None).await,
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(picks, 2);
+        assert_ne!(reserved.port, locked_port);
+        assert!(ActivePort::register(locked_port).is_some());
+        drop(reserved.listener);
+        release_port_lock(reserved.port, reserved.lock);
+        release_port_lock(locked_port, other_lock);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn concurrent_locks_of_a_stale_lock_file() {
+        use std::sync::{Arc, Barrier};
+
+        let port = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        // Left behind by a sandbox that crashed before removing it.
+        std::fs::write(port_lock_path(port), b"stale").unwrap();
+
+        // Each thread opens its own handle, like separate processes would.
+        let barrier = Arc::new(Barrier::new(2));
+        let lockers: Vec<_> = (0..2)
+            .map(|_| {
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    lock_port(port)
+                })
+            })
+            .collect();
+        let mut locks = Vec::new();
+        for locker in lockers {
+            match locker.join().unwrap() {
+                Ok(lock) => locks.extend(lock),
+                Err(err) => panic!("locking the stale lock file failed: {err}"),
+            }
+        }
+        assert_eq!(locks.len(), 1, "exactly one handle should get the lock");
+
+        release_port_lock(port, locks.pop().unwrap());
+        assert!(!port_lock_path(port).exists());
+
+        let lock = lock_port(port).unwrap().unwrap();
+        release_port_lock(port, lock);
+    }
+}
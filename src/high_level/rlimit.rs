@@ -0,0 +1,39 @@
+//! Raising the open files limit, so RocksDB of the sandbox can open as many files as it's
+//! configured to. Too low limits in containers are the most common cause of startup failures.
+
+use super::SandboxConfigError;
+use crate::SandboxError;
+
+// Files the sandbox needs besides the RocksDB ones, e.g. sockets and logs.
+const EXTRA_OPEN_FILES: libc::rlim_t = 1024;
+
+/// Raise the soft `RLIMIT_NOFILE` of the current process to fit `max_open_files`. Child
+/// processes, including the sandbox, inherit it.
+pub(crate) fn raise_open_files_limit(max_open_files: usize) -> Result<(), SandboxError> {
+    let required = max_open_files as libc::rlim_t + EXTRA_OPEN_FILES;
+
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return Err(SandboxError::RuntimeError(std::io::Error::last_os_error()));
+    }
+    if limit.rlim_cur >= required {
+        return Ok(());
+    }
+    if limit.rlim_max < required {
+        return Err(SandboxConfigError::InvalidConfigError(format!(
+            "max_open_files ({max_open_files}) needs an open files limit of {required}, above the hard limit of {}. Lower max_open_files or raise the hard limit",
+            limit.rlim_max
+        ))
+        .into());
+    }
+
+    limit.rlim_cur = required;
+    if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0 {
+        return Err(SandboxError::RuntimeError(std::io::Error::last_os_error()));
+    }
+
+    Ok(())
+}
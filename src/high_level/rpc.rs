@@ -0,0 +1,892 @@
+//! Helpers for calling the JSON-RPC of a running sandbox.
+
+use std::time::Duration;
+
+use base64::Engine;
+use futures::StreamExt;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use super::Sandbox;
+use crate::SandboxError;
+
+/// Maximum number of transactions [`Sandbox::send_txs`] keeps in flight at once.
+const MAX_CONCURRENT_TXS: usize = 16;
+
+/// How often the `wait_for_*` helpers poll the node.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long [`Sandbox::fast_forward`] waits for the node to reach the target height.
+const FAST_FORWARD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How many block summaries [`Sandbox::subscribe_blocks`] buffers for a slow receiver.
+const BLOCK_CHANNEL_CAPACITY: usize = 64;
+
+/// An access key of an account, as returned by [`Sandbox::list_access_keys`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessKeyInfo {
+    /// Public key, e.g. `ed25519:...`
+    pub public_key: String,
+    /// Nonce of the access key
+    pub nonce: u64,
+    /// What the key is allowed to do
+    pub permission: AccessKeyPermission,
+}
+
+/// Permission of an [`AccessKeyInfo`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessKeyPermission {
+    /// Key can sign any transaction on behalf of the account
+    FullAccess,
+    /// Key can only sign function calls to `receiver_id`
+    FunctionCall {
+        /// Remaining allowance in yoctoNEAR, `None` if unlimited
+        allowance: Option<u128>,
+        /// Contract the key can call
+        receiver_id: String,
+        /// Methods the key can call, empty means any method
+        method_names: Vec<String>,
+    },
+}
+
+impl AccessKeyInfo {
+    fn from_rpc(key: &Value) -> Option<Self> {
+        let access_key = &key["access_key"];
+        let permission = match &access_key["permission"] {
+            Value::String(permission) if permission == "FullAccess" => {
+                AccessKeyPermission::FullAccess
+            }
+            permission => {
+                let function_call = &permission["FunctionCall"];
+                let allowance = match &function_call["allowance"] {
+                    Value::Null => None,
+                    allowance => Some(allowance.as_str()?.parse().ok()?),
+                };
+                AccessKeyPermission::FunctionCall {
+                    allowance,
+                    receiver_id: function_call["receiver_id"].as_str()?.to_string(),
+                    method_names: function_call["method_names"]
+                        .as_array()?
+                        .iter()
+                        .map(|method| method.as_str().map(str::to_string))
+                        .collect::<Option<_>>()?,
+                }
+            }
+        };
+
+        Some(Self {
+            public_key: key["public_key"].as_str()?.to_string(),
+            nonce: access_key["nonce"].as_u64()?,
+            permission,
+        })
+    }
+}
+
+/// Error of a JSON-RPC response, as returned by [`Sandbox::rpc_expect_error`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    /// JSON-RPC error code, e.g. `-32000`
+    pub code: i64,
+    /// JSON-RPC error message, e.g. `Server error`
+    pub message: String,
+    /// neard error kind, e.g. `HANDLER_ERROR`
+    pub name: Option<String>,
+    /// neard structured cause with its `name` and `info`
+    pub cause: Option<Value>,
+    /// Legacy free-form error data
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    fn from_rpc(error: &Value) -> Option<Self> {
+        let optional = |key: &str| error.get(key).filter(|value| !value.is_null()).cloned();
+        Some(Self {
+            code: error["code"].as_i64()?,
+            message: error["message"].as_str()?.to_string(),
+            name: error["name"].as_str().map(str::to_string),
+            cause: optional("cause"),
+            data: optional("data"),
+        })
+    }
+}
+
+/// Final execution outcome of a transaction, as returned by [`Sandbox::await_finality`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxOutcome {
+    /// Final status, e.g. `{"SuccessValue": "..."}` or `{"Failure": {...}}`
+    pub status: Value,
+    /// Outcome of converting the transaction into a receipt
+    pub transaction_outcome: Value,
+    /// Outcomes of all receipts produced by the transaction, including their logs
+    pub receipts_outcome: Vec<Value>,
+}
+
+impl TxOutcome {
+    fn from_rpc(result: &Value) -> Option<Self> {
+        Some(Self {
+            status: result.get("status")?.clone(),
+            transaction_outcome: result.get("transaction_outcome")?.clone(),
+            receipts_outcome: result.get("receipts_outcome")?.as_array()?.clone(),
+        })
+    }
+
+    /// Returns whether the transaction and all its receipts succeeded.
+    pub fn is_success(&self) -> bool {
+        self.status.get("Failure").is_none()
+    }
+}
+
+/// Fields of an account to overwrite with [`Sandbox::patch_account`], `None` keeps the
+/// current value.
+#[derive(Debug, Clone, Default)]
+pub struct AccountEdit {
+    /// Liquid balance, in yoctoNEAR
+    pub amount: Option<u128>,
+    /// Locked (staked) balance, in yoctoNEAR
+    pub locked: Option<u128>,
+    /// Base58 encoded hash of the deployed code
+    pub code_hash: Option<String>,
+    /// Storage used by the account, in bytes
+    pub storage_usage: Option<u64>,
+}
+
+impl AccountEdit {
+    fn is_complete(&self) -> bool {
+        self.amount.is_some()
+            && self.locked.is_some()
+            && self.code_hash.is_some()
+            && self.storage_usage.is_some()
+    }
+}
+
+/// State record injected at runtime with [`Sandbox::patch_state`].
+#[derive(Debug, Clone)]
+pub enum StateRecord {
+    /// Creates or overwrites an account. Fields of `edit` left as `None` keep the current
+    /// value of the account, so they must all be set when creating a new one.
+    Account {
+        account_id: String,
+        edit: AccountEdit,
+    },
+    /// Sets a key of the contract storage of `account_id`
+    Data {
+        account_id: String,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    /// Deploys `code` to `account_id` without updating its `code_hash`
+    Contract { account_id: String, code: Vec<u8> },
+    /// Adds a full access key to `account_id`
+    FullAccessKey {
+        account_id: String,
+        public_key: String,
+    },
+}
+
+impl StateRecord {
+    /// Sets the liquid balance, in yoctoNEAR, of an existing account.
+    pub fn balance(account_id: impl Into<String>, amount: u128) -> Self {
+        Self::Account {
+            account_id: account_id.into(),
+            edit: AccountEdit {
+                amount: Some(amount),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Sets `key` to `value` in the contract storage of `account_id`.
+    pub fn data(
+        account_id: impl Into<String>,
+        key: impl Into<Vec<u8>>,
+        value: impl Into<Vec<u8>>,
+    ) -> Self {
+        Self::Data {
+            account_id: account_id.into(),
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    fn to_rpc(&self, account: Option<&Value>) -> Value {
+        let encode = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+        match self {
+            Self::Account { account_id, edit } => {
+                let current =
+                    |key: &str| account.map_or(Value::Null, |account| account[key].clone());
+                let mut record = json!({
+                    "amount": current("amount"),
+                    "locked": current("locked"),
+                    "code_hash": current("code_hash"),
+                    "storage_usage": current("storage_usage"),
+                });
+                if let Some(amount) = edit.amount {
+                    record["amount"] = Value::String(amount.to_string());
+                }
+                if let Some(locked) = edit.locked {
+                    record["locked"] = Value::String(locked.to_string());
+                }
+                if let Some(code_hash) = &edit.code_hash {
+                    record["code_hash"] = Value::String(code_hash.clone());
+                }
+                if let Some(storage_usage) = edit.storage_usage {
+                    record["storage_usage"] = storage_usage.into();
+                }
+
+                json!({
+                    "Account": {
+                        "account_id": account_id,
+                        "account": record,
+                    }
+                })
+            }
+            Self::Data {
+                account_id,
+                key,
+                value,
+            } => json!({
+                "Data": {
+                    "account_id": account_id,
+                    "data_key": encode(key),
+                    "value": encode(value),
+                }
+            }),
+            Self::Contract { account_id, code } => json!({
+                "Contract": {
+                    "account_id": account_id,
+                    "code": encode(code),
+                }
+            }),
+            Self::FullAccessKey {
+                account_id,
+                public_key,
+            } => json!({
+                "AccessKey": {
+                    "account_id": account_id,
+                    "public_key": public_key,
+                    "access_key": {
+                        "nonce": 0,
+                        "permission": "FullAccess",
+                    },
+                }
+            }),
+        }
+    }
+}
+
+/// Summary of a block, as emitted by [`Sandbox::subscribe_blocks`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockSummary {
+    /// Height of the block
+    pub height: u64,
+    /// Base58 encoded hash of the block
+    pub hash: String,
+    /// Number of transactions in the chunks included in the block
+    pub tx_count: usize,
+}
+
+async fn post(
+    client: &reqwest::Client,
+    rpc_addr: &str,
+    body: Value,
+) -> Result<(StatusCode, Value), SandboxError> {
+    let response = client
+        .post(rpc_addr)
+        .json(&body)
+        .send()
+        .await
+        .map_err(SandboxError::RequestError)?;
+
+    let status = response.status();
+    let text = response.text().await.map_err(SandboxError::RequestError)?;
+    let body = serde_json::from_str(&text).unwrap_or(Value::String(text));
+
+    Ok((status, body))
+}
+
+async fn call(
+    client: &reqwest::Client,
+    rpc_addr: &str,
+    method: &str,
+    params: Value,
+) -> Result<Value, SandboxError> {
+    let (status, response) = post(
+        client,
+        rpc_addr,
+        json!({
+            "jsonrpc": "2.0",
+            "id": "sandbox",
+            "method": method,
+            "params": params,
+        }),
+    )
+    .await?;
+
+    if let Some(error) = response.get("error") {
+        return Err(SandboxError::RpcError(error.to_string()));
+    }
+    if !status.is_success() {
+        return Err(SandboxError::RpcError(format!("{status}: {response}")));
+    }
+
+    response
+        .get("result")
+        .cloned()
+        .ok_or_else(|| SandboxError::RpcError(format!("no result in response: {response}")))
+}
+
+fn parse_latest_block_height(status: &Value) -> Result<u64, SandboxError> {
+    status["sync_info"]["latest_block_height"]
+        .as_u64()
+        .ok_or_else(|| {
+            SandboxError::RpcError(format!(
+                "no sync_info.latest_block_height in status: {status}"
+            ))
+        })
+}
+
+/// Fetches `account_id` from the RPC at `rpc_url`, e.g. `https://rpc.testnet.near.org`, as
+/// genesis state records for [`SandboxConfig::imported_records`](crate::SandboxConfig::imported_records):
+/// its `Account`, all its `AccessKey`s and, if `include_code` is set and it has a contract, its
+/// `Contract`. Contract storage isn't fetched.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use near_sandbox_utils::*;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut config = SandboxConfig::builder();
+/// for account_id in ["wrap.testnet", "usdc.fakes.testnet"] {
+///     config.import_records(
+///         fetch_account_records("https://rpc.testnet.near.org", account_id, true).await?,
+///     );
+/// }
+/// let sandbox = Sandbox::start_sandbox_with_config(config.build()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn fetch_account_records(
+    rpc_url: &str,
+    account_id: &str,
+    include_code: bool,
+) -> Result<Vec<Value>, SandboxError> {
+    let client = reqwest::Client::new();
+    let query = |request_type: &str| {
+        json!({
+            "request_type": request_type,
+            "finality": "final",
+            "account_id": account_id,
+        })
+    };
+
+    let account = call(&client, rpc_url, "query", query("view_account")).await?;
+    let mut records = vec![json!({
+        "Account": {
+            "account_id": account_id,
+            "account": {
+                "amount": account["amount"],
+                "locked": account["locked"],
+                "code_hash": account["code_hash"],
+                "storage_usage": account["storage_usage"],
+            },
+        }
+    })];
+
+    let keys = call(&client, rpc_url, "query", query("view_access_key_list")).await?;
+    let keys = keys["keys"].as_array().ok_or_else(|| {
+        SandboxError::RpcError(format!("invalid view_access_key_list response: {keys}"))
+    })?;
+    for key in keys {
+        records.push(json!({
+            "AccessKey": {
+                "account_id": account_id,
+                "public_key": key["public_key"],
+                "access_key": key["access_key"],
+            }
+        }));
+    }
+
+    if include_code && account["code_hash"] != "11111111111111111111111111111111" {
+        let code = call(&client, rpc_url, "query", query("view_code")).await?;
+        records.push(json!({
+            "Contract": {
+                "account_id": account_id,
+                "code": code["code_base64"],
+            }
+        }));
+    }
+
+    Ok(records)
+}
+
+/// Fetches the block selected by `params` together with the transaction count of its new chunks.
+async fn block_summary(
+    client: &reqwest::Client,
+    rpc_addr: &str,
+    params: Value,
+) -> Result<BlockSummary, SandboxError> {
+    let block = call(client, rpc_addr, "block", params).await?;
+    let header = &block["header"];
+    let height = header["height"]
+        .as_u64()
+        .ok_or_else(|| SandboxError::RpcError(format!("invalid block response: {block}")))?;
+
+    let mut tx_count = 0;
+    for chunk in block["chunks"].as_array().into_iter().flatten() {
+        // Chunks not produced at this height are carried over from an earlier block.
+        if chunk["height_included"].as_u64() != Some(height) {
+            continue;
+        }
+        let chunk = call(
+            client,
+            rpc_addr,
+            "chunk",
+            json!({ "chunk_id": chunk["chunk_hash"] }),
+        )
+        .await?;
+        tx_count += chunk["transactions"].as_array().map_or(0, Vec::len);
+    }
+
+    Ok(BlockSummary {
+        height,
+        hash: header["hash"].as_str().unwrap_or_default().to_string(),
+        tx_count,
+    })
+}
+
+impl Sandbox {
+    /// Posts a raw JSON-RPC envelope to the sandbox and returns the HTTP status with the
+    /// response body.
+    ///
+    /// This is the lowest-level RPC helper, useful for testing error responses and methods
+    /// not covered by the typed helpers. A body that isn't valid JSON is returned as a string.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox_utils::*;
+    /// use serde_json::json;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// let (status, body) = sandbox
+    ///     .rpc_post(json!({ "jsonrpc": "2.0", "id": "0", "method": "status", "params": [] }))
+    ///     .await?;
+    /// assert!(status.is_success());
+    /// println!("{body}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn rpc_post(&self, body: Value) -> Result<(StatusCode, Value), SandboxError> {
+        post(&self.rpc_client, &self.rpc_addr, body).await
+    }
+
+    /// Posts a JSON-RPC envelope that's expected to be rejected and returns the error.
+    ///
+    /// Fails with [`SandboxError::RpcError`] if the node unexpectedly succeeds or the response
+    /// isn't a JSON-RPC error. Useful for negative testing of client error handling.
+    pub async fn rpc_expect_error(&self, body: Value) -> Result<RpcError, SandboxError> {
+        let (status, response) = self.rpc_post(body).await?;
+        match response.get("error") {
+            Some(error) => RpcError::from_rpc(error)
+                .ok_or_else(|| SandboxError::RpcError(format!("invalid JSON-RPC error: {error}"))),
+            None => Err(SandboxError::RpcError(format!(
+                "expected a JSON-RPC error, got {status}: {response}"
+            ))),
+        }
+    }
+
+    /// Calls the given JSON-RPC `method` and returns the `result` of the response.
+    pub(crate) async fn rpc_call(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, SandboxError> {
+        call(&self.rpc_client, &self.rpc_addr, method, params).await
+    }
+
+    /// Quick liveness probe: a single `/status` request that must succeed within `timeout`.
+    pub async fn rpc_reachable(&self, timeout: Duration) -> bool {
+        self.rpc_client
+            .get(format!("{}/status", self.rpc_addr))
+            .timeout(timeout)
+            .send()
+            .await
+            .is_ok_and(|response| response.status().is_success())
+    }
+
+    /// Returns the hash of the genesis block, as reported by the `status` RPC.
+    ///
+    /// The bytes are the same as in `near_primitives::hash::CryptoHash`.
+    pub async fn genesis_hash(&self) -> Result<[u8; 32], SandboxError> {
+        let status = self.rpc_call("status", json!([])).await?;
+        let genesis_hash = status["genesis_hash"].as_str().ok_or_else(|| {
+            SandboxError::RpcError(format!("no genesis_hash in status: {status}"))
+        })?;
+
+        let mut hash = [0u8; 32];
+        let len = bs58::decode(genesis_hash)
+            .into(&mut hash)
+            .map_err(|e| SandboxError::RpcError(format!("invalid genesis_hash: {e}")))?;
+        if len != hash.len() {
+            return Err(SandboxError::RpcError(format!(
+                "invalid genesis_hash length: {genesis_hash}"
+            )));
+        }
+
+        Ok(hash)
+    }
+
+    /// Returns the height of the latest block, as reported by the `status` RPC.
+    pub async fn latest_block_height(&self) -> Result<u64, SandboxError> {
+        let status = self.rpc_call("status", json!([])).await?;
+        parse_latest_block_height(&status)
+    }
+
+    /// Waits until the chain reaches the `target` height and returns the latest height.
+    ///
+    /// Unlike startup readiness, which only needs the `status` RPC to respond, this waits for
+    /// actual block production. Returns [`SandboxError::WaitTimeoutError`] if the height isn't
+    /// reached after `timeout`, or [`SandboxError::RpcError`] right away if the status response
+    /// has no `sync_info.latest_block_height`.
+    pub async fn wait_for_block_height(
+        &self,
+        target: u64,
+        timeout: Duration,
+    ) -> Result<u64, SandboxError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        let mut last_height = None;
+        loop {
+            interval.tick().await;
+            if let Ok(status) = self.rpc_call("status", json!([])).await {
+                let height = parse_latest_block_height(&status)?;
+                if height >= target {
+                    return Ok(height);
+                }
+                last_height = Some(height);
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                let observed = last_height.map_or("none".to_string(), |height| height.to_string());
+                return Err(SandboxError::WaitTimeoutError(format!(
+                    "expected block height {target} after {timeout:?}, last observed: {observed}"
+                )));
+            }
+        }
+    }
+
+    /// Skips `blocks` blocks ahead through the `sandbox_fast_forward` RPC, e.g. to test lockups
+    /// or unbonding, and returns the new height.
+    ///
+    /// Returns [`SandboxError::WaitTimeoutError`] if the node doesn't reach the new height in time.
+    pub async fn fast_forward(&self, blocks: u64) -> Result<u64, SandboxError> {
+        let target = self.latest_block_height().await? + blocks;
+        self.rpc_call("sandbox_fast_forward", json!({ "delta_height": blocks }))
+            .await?;
+
+        self.wait_for_block_height(target, FAST_FORWARD_TIMEOUT)
+            .await
+    }
+
+    /// Returns the block at the given height, as returned by the `block` RPC.
+    pub async fn block(&self, height: u64) -> Result<Value, SandboxError> {
+        self.rpc_call("block", json!({ "block_id": height })).await
+    }
+
+    /// Streams a [`BlockSummary`] of every final block produced from now on.
+    ///
+    /// Blocks are fetched in the background by polling the `block` RPC. The channel is bounded,
+    /// so polling pauses while the receiver lags behind, and stops once the receiver is dropped.
+    pub fn subscribe_blocks(&self) -> mpsc::Receiver<BlockSummary> {
+        let (sender, receiver) = mpsc::channel(BLOCK_CHANNEL_CAPACITY);
+        let client = self.rpc_client.clone();
+        let rpc_addr = self.rpc_addr.clone();
+
+        tokio::spawn(async move {
+            let mut next_height = None;
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            while !sender.is_closed() {
+                interval.tick().await;
+                let Ok(latest) =
+                    block_summary(&client, &rpc_addr, json!({ "finality": "final" })).await
+                else {
+                    continue;
+                };
+                let Some(from) = next_height else {
+                    next_height = Some(latest.height + 1);
+                    if sender.send(latest).await.is_err() {
+                        return;
+                    }
+                    continue;
+                };
+
+                for height in from..=latest.height {
+                    // Heights can be skipped, those blocks simply don't exist.
+                    let Ok(summary) =
+                        block_summary(&client, &rpc_addr, json!({ "block_id": height })).await
+                    else {
+                        continue;
+                    };
+                    if sender.send(summary).await.is_err() {
+                        return;
+                    }
+                }
+                next_height = Some(from.max(latest.height + 1));
+            }
+        });
+
+        receiver
+    }
+
+    /// Overwrites fields of an existing account through the `sandbox_patch_state` RPC, e.g. to
+    /// give it a balance or simulate a staked account. Fields not set in `edit` keep their
+    /// current value.
+    ///
+    /// Fails with [`SandboxError::PatchStateError`] if the account doesn't exist, even if all
+    /// fields of `edit` are set. Use [`Sandbox::patch_state`] to create an account.
+    pub async fn patch_account(
+        &self,
+        account_id: &str,
+        edit: AccountEdit,
+    ) -> Result<(), SandboxError> {
+        let account = self
+            .view_account_for_patch(account_id)
+            .await
+            .map_err(|e| match e {
+                SandboxError::RpcError(e) if e.contains("UNKNOWN_ACCOUNT") => {
+                    SandboxError::PatchStateError(format!("account {account_id} doesn't exist"))
+                }
+                e => e,
+            })?;
+        let record = StateRecord::Account {
+            account_id: account_id.to_string(),
+            edit,
+        };
+
+        self.send_patch(vec![record.to_rpc(Some(&account))]).await
+    }
+
+    /// Current state of `account_id`, which unset fields of an [`AccountEdit`] are taken from.
+    async fn view_account_for_patch(&self, account_id: &str) -> Result<Value, SandboxError> {
+        self.rpc_call(
+            "query",
+            json!({
+                "request_type": "view_account",
+                "finality": "optimistic",
+                "account_id": account_id,
+            }),
+        )
+        .await
+    }
+
+    /// Injects `records` into the state through the `sandbox_patch_state` RPC, without sending
+    /// any transactions. Useful to set up fixtures such as contract storage or balances.
+    ///
+    /// Fails with [`SandboxError::PatchStateError`] if the node rejects the patch.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use near_sandbox_utils::{Sandbox, StateRecord};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox = Sandbox::start_sandbox().await?;
+    /// sandbox
+    ///     .patch_state(vec![
+    ///         StateRecord::balance("sandbox", 1_000_000_000_000_000_000_000_000),
+    ///         StateRecord::data("sandbox", b"STATE".to_vec(), b"{}".to_vec()),
+    ///     ])
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn patch_state(&self, records: Vec<StateRecord>) -> Result<(), SandboxError> {
+        let mut patch = Vec::with_capacity(records.len());
+        for record in &records {
+            let account = match record {
+                StateRecord::Account { account_id, edit } if !edit.is_complete() => {
+                    Some(self.view_account_for_patch(account_id).await?)
+                }
+                _ => None,
+            };
+            patch.push(record.to_rpc(account.as_ref()));
+        }
+
+        self.send_patch(patch).await
+    }
+
+    /// Sends `sandbox_patch_state` with records already in the RPC format.
+    async fn send_patch(&self, patch: Vec<Value>) -> Result<(), SandboxError> {
+        self.rpc_call("sandbox_patch_state", json!({ "records": patch }))
+            .await
+            .map_err(|e| match e {
+                SandboxError::RpcError(e) => SandboxError::PatchStateError(e),
+                e => e,
+            })?;
+
+        Ok(())
+    }
+
+    /// Returns all access keys of `account_id` at the final block, using the
+    /// `view_access_key_list` query.
+    pub async fn list_access_keys(
+        &self,
+        account_id: &str,
+    ) -> Result<Vec<AccessKeyInfo>, SandboxError> {
+        let result = self
+            .rpc_call(
+                "query",
+                json!({
+                    "request_type": "view_access_key_list",
+                    "finality": "final",
+                    "account_id": account_id,
+                }),
+            )
+            .await?;
+
+        result["keys"]
+            .as_array()
+            .and_then(|keys| keys.iter().map(AccessKeyInfo::from_rpc).collect())
+            .ok_or_else(|| {
+                SandboxError::RpcError(format!("invalid view_access_key_list response: {result}"))
+            })
+    }
+
+    /// Returns the gas price, in yoctoNEAR, at the given block height or at the latest block
+    /// if `block_height` is `None`.
+    pub async fn gas_price(&self, block_height: Option<u64>) -> Result<u128, SandboxError> {
+        let result = self.rpc_call("gas_price", json!([block_height])).await?;
+        result["gas_price"]
+            .as_str()
+            .and_then(|price| price.parse().ok())
+            .ok_or_else(|| SandboxError::RpcError(format!("invalid gas_price response: {result}")))
+    }
+
+    /// Returns the protocol config in effect at the latest final block, as returned by the
+    /// `EXPERIMENTAL_protocol_config` RPC.
+    ///
+    /// Useful to check that genesis or config tweaks, e.g. gas limits or storage costs, actually
+    /// took effect at the protocol level.
+    pub async fn protocol_config(&self) -> Result<Value, SandboxError> {
+        self.rpc_call(
+            "EXPERIMENTAL_protocol_config",
+            json!({ "finality": "final" }),
+        )
+        .await
+    }
+
+    /// Waits until the node reports it's no longer syncing, e.g. after a restart.
+    ///
+    /// Returns [`SandboxError::WaitTimeoutError`] if the node is still syncing after `timeout`.
+    pub async fn wait_for_sync(&self, timeout: Duration) -> Result<(), SandboxError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Ok(status) = self.rpc_call("status", json!([])).await {
+                if status["sync_info"]["syncing"] == false {
+                    return Ok(());
+                }
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                return Err(SandboxError::WaitTimeoutError(format!(
+                    "node is still syncing after {timeout:?}"
+                )));
+            }
+        }
+    }
+
+    /// Waits until the current validator set, as reported by the `validators` RPC, has `count`
+    /// validators. Validator changes only apply at epoch boundaries, so this can take a while.
+    ///
+    /// Returns [`SandboxError::WaitTimeoutError`] with the last observed count after `timeout`.
+    pub async fn wait_for_validator_count(
+        &self,
+        count: usize,
+        timeout: Duration,
+    ) -> Result<(), SandboxError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        let mut last_count = None;
+        loop {
+            interval.tick().await;
+            if let Ok(validators) = self.rpc_call("validators", json!([null])).await {
+                if let Some(current) = validators["current_validators"].as_array() {
+                    if current.len() == count {
+                        return Ok(());
+                    }
+                    last_count = Some(current.len());
+                }
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                let observed = last_count.map_or("none".to_string(), |count| count.to_string());
+                return Err(SandboxError::WaitTimeoutError(format!(
+                    "expected {count} validators after {timeout:?}, last observed: {observed}"
+                )));
+            }
+        }
+    }
+
+    /// Sends a signed transaction and waits until it's final, returning its execution outcome.
+    ///
+    /// # Arguments
+    /// * `signed_tx` - base64 encoded borsh serialized `SignedTransaction`
+    pub async fn send_tx(&self, signed_tx: &str) -> Result<Value, SandboxError> {
+        self.rpc_call(
+            "send_tx",
+            json!({
+                "signed_tx_base64": signed_tx,
+                "wait_until": "FINAL",
+            }),
+        )
+        .await
+    }
+
+    /// Waits until the transaction is final and returns its full execution outcome, including
+    /// the outcomes and logs of all receipts it produced (`receipts_outcome`).
+    ///
+    /// # Arguments
+    /// * `tx_hash` - base58 encoded hash of the transaction
+    /// * `sender` - account id of the transaction signer
+    pub async fn await_finality(
+        &self,
+        tx_hash: &str,
+        sender: &str,
+    ) -> Result<TxOutcome, SandboxError> {
+        let result = self
+            .rpc_call(
+                "EXPERIMENTAL_tx_status",
+                json!({
+                    "tx_hash": tx_hash,
+                    "sender_account_id": sender,
+                    "wait_until": "FINAL",
+                }),
+            )
+            .await?;
+
+        TxOutcome::from_rpc(&result).ok_or_else(|| {
+            SandboxError::RpcError(format!(
+                "unexpected EXPERIMENTAL_tx_status result: {result}"
+            ))
+        })
+    }
+
+    /// Sends a batch of signed transactions concurrently and waits until all of them are final.
+    ///
+    /// Results are returned in the same order as `txs`, a failure of one transaction doesn't
+    /// affect the others.
+    ///
+    /// # Arguments
+    /// * `txs` - base64 encoded borsh serialized `SignedTransaction`s
+    pub async fn send_txs(&self, txs: Vec<String>) -> Vec<Result<Value, SandboxError>> {
+        futures::stream::iter(txs.iter().map(|tx| self.send_tx(tx)))
+            .buffered(MAX_CONCURRENT_TXS)
+            .collect()
+            .await
+    }
+}
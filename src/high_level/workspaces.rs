@@ -0,0 +1,26 @@
+//! Interop with [`near-workspaces`](https://crates.io/crates/near-workspaces).
+//!
+//! The worker returned here only connects to the node started by [`Sandbox`]; it doesn't own
+//! the node. The sandbox process and its home directory stay managed by [`Sandbox`], so the
+//! worker must not outlive it.
+
+use near_workspaces::network::Sandbox as WorkspacesSandbox;
+use near_workspaces::Worker;
+
+use super::Sandbox;
+use crate::SandboxError;
+
+impl Sandbox {
+    /// Connects a near-workspaces [`Worker`] to this sandbox, so existing near-workspaces
+    /// `Account`/`Contract` based tests can run against it.
+    ///
+    /// The worker's root account is the validator account from `validator_key.json` in the
+    /// sandbox home directory. Requires the `workspaces` feature.
+    pub async fn workspaces_worker(&self) -> Result<Worker<WorkspacesSandbox>, SandboxError> {
+        near_workspaces::sandbox()
+            .rpc_addr(&self.rpc_addr)
+            .home_dir(self.home_dir.path())
+            .await
+            .map_err(SandboxError::WorkspacesError)
+    }
+}
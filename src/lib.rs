@@ -1,15 +1,25 @@
 use binary_install::Cache;
 use fs2::FileExt;
 use tokio::process::{Child, Command};
+use tokio::sync::OnceCell;
 
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
 
 pub mod high_level;
 pub mod sync;
 
 // Re-export important types for better user experience
-pub use high_level::{GenesisAccount, Sandbox, SandboxConfig};
+pub use high_level::{
+    fetch_account_records, AccessKeyInfo, AccessKeyPermission, AccountEdit, BlockSummary,
+    GenesisAccount, GenesisContract, GenesisEconomics, GenesisParams, HomeDir, KeyFile, LogOutput,
+    ReadyPollHook, RpcError, Sandbox, SandboxConfig, SandboxConfigBuilder, StateRecord, TxOutcome,
+};
+
+#[cfg(feature = "generate")]
+pub use high_level::config::implicit_account_id;
 
 // The current version of the sandbox node we want to point to.
 // Should be updated to the latest release of nearcore.
@@ -30,8 +40,22 @@ pub enum SandboxError {
     #[error("Runtime error: {0}")]
     RuntimeError(std::io::Error),
 
-    #[error("Timeout: Sandbox didn't start within provided timeout")]
-    TimeoutError,
+    #[error("Port {port} is already used by another sandbox in this process")]
+    PortAlreadyInUse { port: u16 },
+
+    /// Holds the last lines logged by the sandbox if its output is captured.
+    #[error("Timeout: Sandbox didn't start within provided timeout{}", log_tail(.0))]
+    TimeoutError(Vec<String>),
+
+    /// Holds the last lines logged by the sandbox if its output is captured.
+    #[error("Sandbox exited during startup with {status}{}", log_tail(.logs))]
+    ProcessExited {
+        status: std::process::ExitStatus,
+        logs: Vec<String>,
+    },
+
+    #[error("Timeout: {0}")]
+    WaitTimeoutError(String),
 
     #[error("Error resolving binary: {0}")]
     BinaryError(String),
@@ -47,6 +71,39 @@ pub enum SandboxError {
 
     #[error("Unsupported platform: {0}")]
     UnsupportedPlatformError(String),
+
+    #[error("Unsupported sandbox version: {0}")]
+    UnsupportedVersionError(String),
+
+    #[error("Error while sending RPC request: {0}")]
+    RequestError(reqwest::Error),
+
+    #[error("RPC error: {0}")]
+    RpcError(String),
+
+    #[error("Failed to patch state: {0}")]
+    PatchStateError(String),
+
+    #[error("Transaction error: {0}")]
+    TransactionError(String),
+
+    #[error("Insufficient balance: {0}")]
+    InsufficientBalanceError(String),
+
+    #[error("Sandbox logged {} error(s):\n{}", .0.len(), .0.join("\n"))]
+    LoggedErrors(Vec<String>),
+
+    #[cfg(feature = "workspaces")]
+    #[error("near-workspaces error: {0}")]
+    WorkspacesError(near_workspaces::error::Error),
+}
+
+fn log_tail(lines: &[String]) -> String {
+    if lines.is_empty() {
+        return String::new();
+    }
+
+    format!(", last logs:\n{}", lines.join("\n"))
 }
 
 const fn platform() -> Option<&'static str> {
@@ -71,28 +128,49 @@ fn local_addr(port: u16) -> String {
     format!("0.0.0.0:{}", port)
 }
 
+const DEFAULT_DOWNLOAD_URL: &str =
+    "https://s3-us-west-1.amazonaws.com/build.nearprotocol.com/nearcore";
+
 // if the `SANDBOX_ARTIFACT_URL` env var is set, we short-circuit and use that.
+// Otherwise `NEAR_SANDBOX_DOWNLOAD_URL` can point to a mirror, either as a base URL laid out
+// like the upstream one, or as a template with `{platform}` and `{version}` placeholders.
 fn bin_url(version: &str) -> Option<String> {
     if let Ok(val) = std::env::var("SANDBOX_ARTIFACT_URL") {
         return Some(val);
     }
 
+    let platform = platform()?;
+    let base_url = std::env::var("NEAR_SANDBOX_DOWNLOAD_URL")
+        .unwrap_or_else(|_| DEFAULT_DOWNLOAD_URL.to_string());
+    if base_url.contains("{platform}") || base_url.contains("{version}") {
+        return Some(
+            base_url
+                .replace("{platform}", platform)
+                .replace("{version}", version),
+        );
+    }
+
     Some(format!(
-        "https://s3-us-west-1.amazonaws.com/build.nearprotocol.com/nearcore/{}/{}/near-sandbox.tar.gz",
-        platform()?,
+        "{}/{}/{}/near-sandbox.tar.gz",
+        base_url.trim_end_matches('/'),
+        platform,
         version,
     ))
 }
 
-// Returns a path to the binary in the form of: `{home}/.near/near-sandbox-{version}` || `{$OUT_DIR}/.near/near-sandbox-{version}`
+// Returns a path to the binary in the form of: `{home}/.near/near-sandbox-{version}` || `{$OUT_DIR}/.near/near-sandbox-{version}`,
+// or `{$NEAR_SANDBOX_CACHE_DIR}/near-sandbox-{version}` if set, e.g. to a directory cached by CI.
 fn download_path(version: &str) -> PathBuf {
-    let mut out = if cfg!(feature = "global_install") {
-        home::home_dir().expect("could not retrieve home_dir")
+    let mut out = if let Ok(cache_dir) = std::env::var("NEAR_SANDBOX_CACHE_DIR") {
+        PathBuf::from(cache_dir)
+    } else if cfg!(feature = "global_install") {
+        home::home_dir()
+            .expect("could not retrieve home_dir")
+            .join(".near")
     } else {
-        PathBuf::from(env!("OUT_DIR"))
+        PathBuf::from(env!("OUT_DIR")).join(".near")
     };
 
-    out.push(".near");
     out.push(format!("near-sandbox-{}", normalize_name(version)));
     if !out.exists() {
         std::fs::create_dir_all(&out).expect("could not create download path");
@@ -105,12 +183,7 @@ fn download_path(version: &str) -> PathBuf {
 pub fn bin_path(version: &str) -> Result<PathBuf, SandboxError> {
     if let Ok(path) = std::env::var("NEAR_SANDBOX_BIN_PATH") {
         let path = PathBuf::from(path);
-        if !path.exists() {
-            return Err(SandboxError::BinaryError(format!(
-                "{} does not exists",
-                path.display()
-            )));
-        }
+        check_binary(&path)?;
         return Ok(path);
     }
 
@@ -120,6 +193,32 @@ pub fn bin_path(version: &str) -> Result<PathBuf, SandboxError> {
     Ok(buf)
 }
 
+/// Checks that a user supplied sandbox binary exists and is executable.
+pub(crate) fn check_binary(path: &Path) -> Result<(), SandboxError> {
+    let metadata = std::fs::metadata(path).map_err(|e| {
+        SandboxError::BinaryError(format!("{} does not exists: {}", path.display(), e))
+    })?;
+    if !metadata.is_file() {
+        return Err(SandboxError::BinaryError(format!(
+            "{} is not a file",
+            path.display()
+        )));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Err(SandboxError::BinaryError(format!(
+                "{} is not executable",
+                path.display()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 fn normalize_name(input: &str) -> String {
     input.replace('/', "_")
 }
@@ -162,6 +261,76 @@ pub fn install() -> Result<PathBuf, SandboxError> {
     ensure_sandbox_bin_with_version(DEFAULT_NEAR_SANDBOX_VERSION)
 }
 
+/// Binaries resolved in this process, one cell per version.
+static RESOLVED_BINS: Mutex<BTreeMap<String, Arc<OnceCell<PathBuf>>>> = Mutex::new(BTreeMap::new());
+
+/// Same as [`ensure_sandbox_bin_with_version`], but resolves each version only once per
+/// process. Concurrent callers for the same version share a single resolution.
+pub(crate) async fn ensure_sandbox_bin_once(version: &str) -> Result<PathBuf, SandboxError> {
+    let cell = RESOLVED_BINS
+        .lock()
+        .expect("resolved binaries lock poisoned")
+        .entry(version.to_string())
+        .or_default()
+        .clone();
+
+    cell.get_or_try_init(|| ensure_sandbox_bin_async(version))
+        .await
+        .cloned()
+}
+
+/// Same as [`ensure_sandbox_bin_with_version`], but downloads and extracts the binary on a
+/// blocking thread, so it doesn't stall other tasks of the async runtime.
+pub async fn ensure_sandbox_bin_async(version: &str) -> Result<PathBuf, SandboxError> {
+    let version = version.to_string();
+    tokio::task::spawn_blocking(move || ensure_sandbox_bin_with_version(&version))
+        .await
+        .map_err(|e| SandboxError::RuntimeError(std::io::Error::other(e)))?
+}
+
+/// Number of downloads currently running in this process, see [`DownloadPermit`].
+static ACTIVE_DOWNLOADS: Mutex<usize> = Mutex::new(0);
+static DOWNLOAD_FINISHED: Condvar = Condvar::new();
+
+/// Limits how many sandbox binaries are downloaded at once within the process, so suites
+/// starting many sandboxes with a cold cache don't all download at the same time and time out.
+/// The limit is taken from `NEAR_SANDBOX_MAX_CONCURRENT_DOWNLOADS` and defaults to 1.
+struct DownloadPermit;
+
+impl DownloadPermit {
+    fn acquire() -> Result<Self, SandboxError> {
+        let limit = match high_level::config::parse_env::<usize>(
+            "NEAR_SANDBOX_MAX_CONCURRENT_DOWNLOADS",
+        )? {
+            Some(0) => {
+                return Err(high_level::SandboxConfigError::EnvParseError(
+                    "NEAR_SANDBOX_MAX_CONCURRENT_DOWNLOADS must be at least 1".to_string(),
+                )
+                .into())
+            }
+            Some(limit) => limit,
+            None => 1,
+        };
+
+        let mut active = ACTIVE_DOWNLOADS.lock().expect("download lock poisoned");
+        while *active >= limit {
+            active = DOWNLOAD_FINISHED
+                .wait(active)
+                .expect("download lock poisoned");
+        }
+        *active += 1;
+
+        Ok(Self)
+    }
+}
+
+impl Drop for DownloadPermit {
+    fn drop(&mut self) {
+        *ACTIVE_DOWNLOADS.lock().expect("download lock poisoned") -= 1;
+        DOWNLOAD_FINISHED.notify_one();
+    }
+}
+
 fn installable(bin_path: &Path) -> Result<Option<std::fs::File>, SandboxError> {
     // Sandbox bin already exists
     if bin_path.exists() {
@@ -217,7 +386,10 @@ pub fn init(home_dir: impl AsRef<Path>) -> Result<Child, SandboxError> {
 pub fn ensure_sandbox_bin_with_version(version: &str) -> Result<PathBuf, SandboxError> {
     let mut bin_path = bin_path(version)?;
     if let Some(lockfile) = installable(&bin_path)? {
-        bin_path = install_with_version(version)?;
+        bin_path = {
+            let _permit = DownloadPermit::acquire()?;
+            install_with_version(version)?
+        };
         std::env::set_var("NEAR_SANDBOX_BIN_PATH", bin_path.as_os_str());
         fs2::FileExt::unlock(&lockfile).map_err(SandboxError::FileError)?;
     }
@@ -229,14 +401,26 @@ pub fn run_with_options_with_version(
     options: &[&str],
     version: &str,
 ) -> Result<Child, SandboxError> {
-    let bin_path = ensure_sandbox_bin_with_version(version)?;
-    Command::new(&bin_path)
+    sandbox_command_with_version(version)?
         .args(options)
-        .envs(crate::log_vars())
         .spawn()
         .map_err(SandboxError::RuntimeError)
 }
 
+/// Returns a command for the sandbox binary of the given version with the log
+/// environment variables already set, so callers can further configure it before spawning.
+pub(crate) fn sandbox_command_with_version(version: &str) -> Result<Command, SandboxError> {
+    let bin_path = ensure_sandbox_bin_with_version(version)?;
+    Ok(sandbox_command(&bin_path))
+}
+
+/// Same as [`sandbox_command_with_version`], but for an already resolved binary.
+pub(crate) fn sandbox_command(bin_path: &Path) -> Command {
+    let mut command = Command::new(bin_path);
+    command.envs(crate::log_vars());
+    command
+}
+
 pub fn run_with_version(
     home_dir: impl AsRef<Path>,
     rpc_port: u16,
@@ -262,9 +446,16 @@ pub fn run_with_version(
 /// Initialize a sandbox node with the provided version and home directory.
 pub fn init_with_version(home_dir: impl AsRef<Path>, version: &str) -> Result<Child, SandboxError> {
     let bin_path = ensure_sandbox_bin_with_version(version)?;
+    init_with_bin(home_dir, &bin_path)
+}
+
+/// Initialize a sandbox node in the home directory with an already resolved binary.
+pub(crate) fn init_with_bin(
+    home_dir: impl AsRef<Path>,
+    bin_path: &Path,
+) -> Result<Child, SandboxError> {
     let home_dir = home_dir.as_ref().to_str().unwrap();
-    Command::new(&bin_path)
-        .envs(log_vars())
+    sandbox_command(bin_path)
         .args(["--home", home_dir, "init", "--fast"])
         .spawn()
         .map_err(SandboxError::RuntimeError)
@@ -0,0 +1,22 @@
+use anyhow::Result;
+use near_sandbox_utils::{Sandbox, SandboxConfig};
+
+#[tokio::test]
+async fn metrics_are_served_on_the_configured_port() -> Result<()> {
+    let metrics_port = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        listener.local_addr()?.port()
+    };
+    let config = SandboxConfig::builder().metrics_port(metrics_port).build();
+    let sandbox = Sandbox::start_sandbox_with_config(config).await?;
+
+    let metrics_addr = sandbox.metrics_addr().expect("metrics port is configured");
+    assert!(metrics_addr.ends_with(&format!(":{metrics_port}")));
+
+    let response = reqwest::get(format!("{metrics_addr}/metrics")).await?;
+    assert!(response.status().is_success());
+    let metrics = response.text().await?;
+    assert!(metrics.contains("# TYPE"), "unexpected metrics: {metrics}");
+
+    Ok(())
+}
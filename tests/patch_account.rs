@@ -0,0 +1,26 @@
+use anyhow::Result;
+use near_sandbox_utils::{AccountEdit, Sandbox, SandboxError};
+
+#[tokio::test]
+async fn complete_edit_of_a_missing_account_is_rejected() -> Result<()> {
+    let sandbox = Sandbox::start_sandbox().await?;
+    let edit = AccountEdit {
+        amount: Some(1_000),
+        locked: Some(0),
+        code_hash: Some("11111111111111111111111111111111".to_string()),
+        storage_usage: Some(182),
+    };
+
+    let err = sandbox
+        .patch_account("missing.sandbox", edit)
+        .await
+        .expect_err("patched an account that doesn't exist");
+
+    match err {
+        SandboxError::PatchStateError(message) => {
+            assert!(message.contains("missing.sandbox"), "{message}");
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+    Ok(())
+}